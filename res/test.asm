@@ -3,14 +3,14 @@ LI	R0,		0xA
 LI	0x12,		0xA
 LI	R1,		@Table
 ORI R0, 0xB
-(0x001) Boucle
+(0x009) Boucle
 MOV	*R1+,		@Afficheur
 DEC	R0
 SLL 3, R2
 JNE	-0x3
 MOV *R1+, *R2+
 MOV @Table, @Init
-(0x10) Fin
+(0x12) Fin
 JMP	-1
 ROI
 CMP R1, R0
@@ -0,0 +1,1316 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::parser::{
+    self, address::Address, directive::Directive, identifier::is_reserved, immediate::Immediate,
+    instruction::Instruction, label::Label, operand::Operand, pseudo::PseudoInstruction, SyntaxError,
+};
+use crate::diagnostic::{Diagnostic, Severity};
+use crate::memory::{MemoryError, MemoryMap};
+use crate::placement::{self, PlacementError, Region};
+use crate::symbols::{self, SymbolError};
+use crate::validation::{self, ValidationError};
+
+/// Meaningful content of a source line.
+#[derive(Debug, PartialEq)]
+pub enum Item {
+    Label(Label),
+    Instruction(Instruction),
+    /// Switch to the named section.
+    Section(String),
+}
+
+/// Block of consecutive words, started by a section switch or a pinned label.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Section {
+    pub name: String,
+    /// Fixed address, `None` when the linker may relocate the block.
+    pub origin: Option<u16>,
+    /// Address of the first word as laid out by the assembler.
+    pub start: u16,
+}
+
+/// Instruction placed at its final address.
+#[derive(Debug, PartialEq)]
+pub struct Statement {
+    pub line: usize,
+    pub address: u16,
+    pub instruction: Instruction,
+    /// Index of the block holding the statement in `Program::sections`.
+    pub section: usize,
+    /// Index of the item the statement was laid out from.
+    item: usize,
+}
+
+/// Lines between a `.timing_begin` and its `.timing_end`.
+#[derive(Debug, PartialEq)]
+pub struct TimingRegion {
+    pub name: Option<String>,
+    /// Lines of the two directives.
+    pub begin: usize,
+    pub end: usize,
+}
+
+/// Block between `.test` and `.test_end`, run by the `test` subcommand.
+#[derive(Debug, PartialEq)]
+pub struct UnitTest {
+    pub name: String,
+    pub line: usize,
+    /// `.set`, `.call` and `.expect` directives by line.
+    pub body: Vec<(usize, Directive)>,
+}
+
+impl UnitTest {
+    /// Symbols the test refers to, by line.
+    pub fn symbols(&self) -> Vec<(usize, &str)> {
+        let mut symbols = vec![];
+        for (line, directive) in &self.body {
+            match directive {
+                Directive::Call(name) => symbols.push((*line, name.as_str())),
+                Directive::Set(location, value) | Directive::Expect(location, value) => {
+                    if let Operand::NextWord(Address::Symbolic(name)) = location {
+                        symbols.push((*line, name.as_str()));
+                    }
+                    if let Immediate::Address(Address::Symbolic(name)) = value {
+                        symbols.push((*line, name.as_str()));
+                    }
+                }
+                _ => (),
+            }
+        }
+        symbols
+    }
+}
+
+impl Statement {
+    /// Address the statement jumps to, when it is a jump and it is known.
+    pub fn target(&self, symbols: &HashMap<String, u16>) -> Option<u16> {
+        match &self.instruction {
+            Instruction::VI { displacement, .. } => displacement
+                .resolve_relative(self.address, symbols)
+                .map(|displacement| self.address.wrapping_add(1).wrapping_add(displacement as u16)),
+            Instruction::V {
+                tsd: Operand::NextWord(address),
+                ..
+            } => address.resolve(symbols),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct Program {
+    pub statements: Vec<Statement>,
+    pub symbols: HashMap<String, u16>,
+    /// Blocks of code, the first one is not pinned unless a memory map is
+    /// used and may be relocated by the linker.
+    pub sections: Vec<Section>,
+    /// Block each symbol is defined in.
+    pub symbol_sections: HashMap<String, usize>,
+    /// Size of the target memory in words.
+    pub memory_size: u32,
+    /// Undefined symbols are left to the linker instead of being reported.
+    pub relocatable: bool,
+    /// Labels exported to the other modules, with the line declaring them.
+    pub globals: Vec<(usize, String)>,
+    /// Regions whose cycles are reported, in the order they end.
+    pub timing: Vec<TimingRegion>,
+    pub tests: Vec<UnitTest>,
+    memory_map: Option<MemoryMap>,
+    /// Constants defined outside the source.
+    defines: Vec<(String, u16)>,
+    items: Vec<(usize, Item)>,
+    /// Items expanded from pseudo instructions choosing their jump by range.
+    relaxed: HashSet<usize>,
+    /// Lines that could not be parsed.
+    syntax_errors: Vec<(usize, SyntaxError)>,
+}
+
+impl Program {
+    /// Parse a whole source file without its comments, lines that cannot be
+    /// parsed are ignored and those starting with an unknown word are
+    /// reported.
+    pub fn parse(source: &str) -> Program {
+        let mut items = vec![];
+        let mut relaxed = HashSet::new();
+        let mut globals = vec![];
+        let mut syntax_errors = vec![];
+        let mut timing = vec![];
+        let mut open: Vec<(usize, Option<String>)> = vec![];
+        let mut tests = vec![];
+        let mut test: Option<UnitTest> = None;
+
+        for (number, line) in source.lines().enumerate() {
+            let (line, _) = parser::split_comment(line);
+            if line.trim().is_empty() {
+                continue;
+            }
+            if let Some(current) = &mut test {
+                match parser::parse_directive(line) {
+                    Ok((_, Directive::TestEnd)) => tests.extend(test.take()),
+                    Ok((_, step @ (Directive::Set(..) | Directive::Call(_) | Directive::Expect(..)))) => {
+                        current.body.push((number + 1, step))
+                    }
+                    _ => syntax_errors.push((number + 1, SyntaxError::InTest)),
+                }
+                continue;
+            }
+            let line = match parser::parse_label(line) {
+                Ok((rest, label)) => {
+                    items.push((number + 1, Item::Label(label)));
+                    rest
+                }
+                Err(_) => line,
+            };
+            if let Ok((_, directive)) = parser::parse_directive(line) {
+                match directive {
+                    Directive::Section(name) => items.push((number + 1, Item::Section(name))),
+                    Directive::Global(name) => globals.push((number + 1, name)),
+                    Directive::TimingBegin(name) => open.push((number + 1, name)),
+                    Directive::TimingEnd => match open.pop() {
+                        Some((begin, name)) => timing.push(TimingRegion {
+                            name,
+                            begin,
+                            end: number + 1,
+                        }),
+                        None => syntax_errors.push((number + 1, SyntaxError::UnmatchedTimingEnd)),
+                    },
+                    Directive::Test(name) => {
+                        test = Some(UnitTest {
+                            name,
+                            line: number + 1,
+                            body: vec![],
+                        })
+                    }
+                    Directive::TestEnd => syntax_errors.push((number + 1, SyntaxError::UnmatchedTestEnd)),
+                    Directive::Set(..) | Directive::Call(_) | Directive::Expect(..) => {
+                        let name = directive.to_string();
+                        let name = name.split(' ').next().unwrap_or_default().to_owned();
+                        syntax_errors.push((number + 1, SyntaxError::OutsideTest(name)))
+                    }
+                    // Expanded when the source is loaded.
+                    Directive::Include(_) => (),
+                }
+            } else if let Ok((_, pseudo)) = parser::parse_pseudo(line) {
+                match expand(number + 1, &pseudo) {
+                    Ok(expansion) => {
+                        let start = items.len();
+                        items.extend(expansion);
+                        if pseudo.pseudo.relax {
+                            relaxed.extend(start..items.len());
+                        }
+                    }
+                    Err(error) => syntax_errors.push((number + 1, error)),
+                }
+            } else if let Ok((_, instruction)) = parser::parse_line(line) {
+                items.push((number + 1, Item::Instruction(instruction)));
+            } else if let Some(error) = parser::diagnose(line) {
+                syntax_errors.push((number + 1, error));
+            }
+        }
+
+        for (line, name) in open {
+            syntax_errors.push((line, SyntaxError::UnterminatedTiming(name)));
+        }
+        if let Some(test) = test {
+            syntax_errors.push((test.line, SyntaxError::UnterminatedTest(test.name)));
+        }
+        syntax_errors.sort_by_key(|(line, _)| *line);
+
+        let mut program = Program::new(items, relaxed, syntax_errors);
+        program.timing = timing;
+        program.globals = globals;
+        program.tests = tests;
+        program
+    }
+
+    fn new(
+        mut items: Vec<(usize, Item)>,
+        relaxed: HashSet<usize>,
+        syntax_errors: Vec<(usize, SyntaxError)>,
+    ) -> Program {
+        scope(&mut items);
+        let mut program = Program {
+            items,
+            relaxed,
+            syntax_errors,
+            memory_size: placement::MEMORY_SIZE,
+            ..Default::default()
+        };
+        program.place();
+        program
+    }
+
+    /// Place the sections into the regions of the memory map.
+    pub fn set_memory_map(&mut self, map: MemoryMap) {
+        self.memory_map = Some(map);
+        self.place();
+    }
+
+    /// Define a constant symbol, as given on the command line.
+    pub fn define(&mut self, name: &str, value: u16) {
+        self.defines.push((name.to_owned(), value));
+        self.place();
+    }
+
+    /// Lay the program out, pseudo instructions such as BRA pick their jump
+    /// by range.
+    fn place(&mut self) {
+        self.layout();
+        self.relax_items(false);
+    }
+
+    /// Address each section starts at. Sections follow each other in order
+    /// of appearance, from the origin of their region with a memory map and
+    /// from address 0 otherwise. Words under a pinned label do not count.
+    fn bases(&self) -> HashMap<String, u16> {
+        let mut sizes: Vec<(&str, u32)> = vec![(".text", 0)];
+        let mut pinned: HashSet<&str> = HashSet::new();
+        let mut current = ".text";
+        for (_, item) in &self.items {
+            match item {
+                Item::Section(section) => {
+                    current = section;
+                    if !sizes.iter().any(|(name, _)| *name == current) {
+                        sizes.push((current, 0));
+                    }
+                }
+                Item::Label(Label {
+                    address: Some(_), ..
+                }) => {
+                    pinned.insert(current);
+                }
+                Item::Instruction(instruction) if !pinned.contains(current) => {
+                    let size = sizes.iter_mut().find(|(name, _)| *name == current);
+                    size.expect("Section without size").1 += u32::from(instruction.size());
+                }
+                _ => (),
+            }
+        }
+
+        let map = self.memory_map.as_ref();
+        let mut ends: HashMap<Option<&str>, u32> = HashMap::new();
+        let mut bases = HashMap::new();
+        for (name, size) in sizes {
+            let region = map.and_then(|map| map.section_region(name));
+            let end = ends
+                .entry(region.map(|region| region.name.as_str()))
+                .or_insert_with(|| region.map_or(0, |region| region.origin.into()));
+            bases.insert(name.to_owned(), *end as u16);
+            *end += size;
+        }
+        bases
+    }
+
+    /// Assign an address to every label and instruction.
+    ///
+    /// Each section keeps its own location so switching back to a section
+    /// resumes where it stopped.
+    fn layout(&mut self) {
+        let mut locations = self.bases();
+        self.symbols.clear();
+        self.symbol_sections.clear();
+        self.statements.clear();
+
+        let map = self.memory_map.as_ref();
+        let mut name = ".text".to_owned();
+        let mut location: u16 = locations[&name];
+        self.sections = vec![Section {
+            name: name.clone(),
+            origin: map.map(|_| location),
+            start: location,
+        }];
+        for (symbol, value) in map.iter().flat_map(|map| &map.symbols).chain(&self.defines) {
+            self.symbols.insert(symbol.clone(), *value);
+        }
+
+        for (index, (line, item)) in self.items.iter().enumerate() {
+            match item {
+                Item::Section(section) => {
+                    locations.insert(name, location);
+                    location = locations[section];
+                    name = section.clone();
+                    self.sections.push(Section {
+                        name: name.clone(),
+                        origin: map.map(|_| location),
+                        start: location,
+                    });
+                }
+                Item::Label(label) => {
+                    if let Some(address) = label.address {
+                        location = address;
+                        self.sections.push(Section {
+                            name: name.clone(),
+                            origin: Some(address),
+                            start: address,
+                        });
+                    }
+                    self.symbols.insert(label.name.clone(), location);
+                    self.symbol_sections
+                        .insert(label.name.clone(), self.sections.len() - 1);
+                }
+                Item::Instruction(instruction) => {
+                    self.statements.push(Statement {
+                        line: *line,
+                        address: location,
+                        instruction: instruction.clone(),
+                        section: self.sections.len() - 1,
+                        item: index,
+                    });
+                    location = location.wrapping_add(instruction.size());
+                }
+            }
+        }
+    }
+
+    /// Replace relative jumps whose target is out of range by an inverted
+    /// short jump over an absolute `B`, until addresses stop moving.
+    pub fn relax(&mut self) {
+        self.relax_items(true)
+    }
+
+    /// Relax every statement, or only those expanded from pseudo
+    /// instructions choosing their jump by range.
+    fn relax_items(&mut self, all: bool) {
+        loop {
+            let mut promoted = vec![];
+
+            for statement in &self.statements {
+                if !all && !self.relaxed.contains(&statement.item) {
+                    continue;
+                }
+                let in_range = validation::validate(
+                    &statement.instruction,
+                    statement.address,
+                    &self.symbols,
+                )
+                .iter()
+                .all(|error| !matches!(error, ValidationError::Displacement(_)));
+                if in_range {
+                    continue;
+                }
+                if let Some(instructions) = promote(&statement.instruction) {
+                    promoted.push((statement.item, statement.line, instructions));
+                }
+            }
+
+            if promoted.is_empty() {
+                break;
+            }
+            // From the last item, so that the indices left to replace hold.
+            promoted.sort_by_key(|(item, _, _)| *item);
+            for (item, line, instructions) in promoted.into_iter().rev() {
+                let added = instructions.len() - 1;
+                let items = instructions.into_iter().map(|instruction| (line, Item::Instruction(instruction)));
+                self.items.splice(item..=item, items);
+                self.relaxed = self
+                    .relaxed
+                    .iter()
+                    .map(|&index| if index > item { index + added } else { index })
+                    .collect();
+            }
+            self.layout();
+        }
+    }
+
+    /// Every label along with the line defining it, under its scoped name.
+    pub fn labels(&self) -> impl Iterator<Item = (usize, &Label)> {
+        self.items.iter().filter_map(|(line, item)| match item {
+            Item::Label(label) => Some((*line, label)),
+            _ => None,
+        })
+    }
+
+    /// Whether a label or a section switch comes right before a statement,
+    /// which may then be reached other than by falling through.
+    pub fn starts_block(&self, statement: usize) -> bool {
+        let Some(previous) = statement.checked_sub(1) else {
+            return true;
+        };
+        self.items[self.statements[previous].item + 1..self.statements[statement].item]
+            .iter()
+            .any(|(_, item)| matches!(item, Item::Label(_) | Item::Section(_)))
+    }
+
+    pub fn validate(&self) -> Vec<(usize, ValidationError)> {
+        let labels = self.items.iter().filter_map(|(line, item)| match item {
+            Item::Label(label) if is_reserved(&label.name) => {
+                Some((*line, ValidationError::Reserved(label.name.clone())))
+            }
+            _ => None,
+        });
+        let instructions = self.statements.iter().flat_map(|statement| {
+            validation::validate(&statement.instruction, statement.address, &self.symbols)
+                .into_iter()
+                .map(|error| (statement.line, error))
+        });
+
+        let mut errors: Vec<(usize, ValidationError)> = labels.chain(instructions).collect();
+        errors.sort_by_key(|(line, _)| *line);
+        errors
+    }
+
+    /// Report undefined, duplicate and unused symbols.
+    pub fn check_symbols(&self) -> Vec<(usize, SymbolError)> {
+        let mut errors = vec![];
+        let mut definitions: Vec<(usize, &str)> = vec![];
+        let predefined: HashSet<&str> = self
+            .memory_map
+            .iter()
+            .flat_map(|map| &map.symbols)
+            .map(|(name, _)| name.as_str())
+            .collect();
+        for (line, item) in &self.items {
+            if let Item::Label(label) = item {
+                if predefined.contains(label.name.as_str()) {
+                    errors.push((*line, SymbolError::Predefined(label.name.clone())));
+                }
+                if self.defines.iter().any(|(name, _)| *name == label.name) {
+                    errors.push((*line, SymbolError::Defined(label.name.clone())));
+                }
+                match definitions.iter().find(|(_, name)| *name == label.name) {
+                    Some((first, _)) => errors.push((
+                        *line,
+                        SymbolError::Duplicate {
+                            name: label.name.clone(),
+                            first: *first,
+                        },
+                    )),
+                    None => definitions.push((*line, &label.name)),
+                }
+            }
+        }
+
+        let mut used = HashSet::new();
+        for statement in &self.statements {
+            for address in statement.instruction.addresses() {
+                if let Address::Symbolic(name) | Address::RelativeSymbolic(name) = address {
+                    used.insert(name.as_str());
+                    if !self.symbols.contains_key(name) && !is_reserved(name) && !self.relocatable {
+                        errors.push((
+                            statement.line,
+                            SymbolError::Undefined {
+                                name: name.clone(),
+                                suggestion: symbols::suggest(name, self.symbols.keys()),
+                            },
+                        ));
+                    }
+                }
+            }
+        }
+
+        for (line, name) in &self.globals {
+            used.insert(name);
+            if !self.symbols.contains_key(name) {
+                errors.push((
+                    *line,
+                    SymbolError::Undefined {
+                        name: name.clone(),
+                        suggestion: symbols::suggest(name, self.symbols.keys()),
+                    },
+                ));
+            }
+        }
+
+        for test in &self.tests {
+            for (line, name) in test.symbols() {
+                used.insert(name);
+                if !self.symbols.contains_key(name) {
+                    errors.push((
+                        line,
+                        SymbolError::Undefined {
+                            name: name.to_owned(),
+                            suggestion: symbols::suggest(name, self.symbols.keys()),
+                        },
+                    ));
+                }
+            }
+        }
+
+        for (line, name) in definitions {
+            if !used.contains(name) {
+                errors.push((line, SymbolError::Unused(name.to_owned())));
+            }
+        }
+
+        errors.sort_by_key(|(line, _)| *line);
+        errors
+    }
+
+    /// Report instructions sharing words or placed past the end of memory.
+    pub fn check_placement(&self) -> Vec<(usize, PlacementError)> {
+        let regions: Vec<Region> = self
+            .statements
+            .iter()
+            .map(|statement| Region {
+                line: statement.line,
+                start: statement.address.into(),
+                end: u32::from(statement.address) + u32::from(statement.instruction.size()),
+            })
+            .collect();
+        placement::check(&regions, self.memory_size)
+    }
+
+    /// Report sections missing from the memory map or overflowing their
+    /// region, once per section.
+    pub fn check_memory(&self) -> Vec<(usize, MemoryError)> {
+        let Some(map) = &self.memory_map else {
+            return vec![];
+        };
+        let mut errors = vec![];
+        let mut reported: HashSet<(&str, u8)> = HashSet::new();
+
+        for statement in &self.statements {
+            let section = &self.sections[statement.section].name;
+            let mut report = |kind: u8, error: MemoryError| {
+                if reported.insert((section, kind)) {
+                    errors.push((statement.line, error));
+                }
+            };
+            let Some(region) = map.section_region(section) else {
+                report(0, MemoryError::Unmapped(section.clone()));
+                continue;
+            };
+            let end = u32::from(statement.address) + u32::from(statement.instruction.size());
+            if statement.address < region.origin || end > region.end() {
+                report(
+                    1,
+                    MemoryError::Overflow {
+                        section: section.clone(),
+                        region: region.name.clone(),
+                        end: region.end(),
+                    },
+                );
+            }
+            if !region.executable {
+                report(
+                    2,
+                    MemoryError::NotExecutable {
+                        section: section.clone(),
+                        region: region.name.clone(),
+                    },
+                );
+            }
+        }
+
+        errors.sort_by_key(|(line, _)| *line);
+        errors
+    }
+
+    /// Every error and warning found in the program, by line.
+    pub fn diagnostics(&self) -> Vec<Diagnostic> {
+        let mut diagnostics: Vec<Diagnostic> = self
+            .syntax_errors
+            .iter()
+            .map(|(line, error)| Diagnostic::error(*line, error))
+            .chain(
+                self.validate()
+                    .into_iter()
+                    .map(|(line, error)| Diagnostic::error(line, error)),
+            )
+            .chain(
+                self.check_symbols()
+                    .into_iter()
+                    .map(|(line, error)| match error.severity() {
+                        Severity::Error => Diagnostic::error(line, error),
+                        Severity::Warning => Diagnostic::warning(line, error),
+                    }),
+            )
+            .chain(
+                self.check_placement()
+                    .into_iter()
+                    .map(|(line, error)| match error.severity() {
+                        Severity::Error => Diagnostic::error(line, error),
+                        Severity::Warning => Diagnostic::warning(line, error),
+                    }),
+            )
+            .chain(
+                self.check_memory()
+                    .into_iter()
+                    .map(|(line, error)| match error.severity() {
+                        Severity::Error => Diagnostic::error(line, error),
+                        Severity::Warning => Diagnostic::warning(line, error),
+                    }),
+            )
+            .collect();
+        diagnostics.sort_by_key(|diagnostic| diagnostic.line);
+        diagnostics
+    }
+}
+
+/// Give local and anonymous labels a unique name and rewrite the references
+/// to them accordingly.
+fn scope(items: &mut [(usize, Item)]) {
+    // Anonymous labels are renamed `<number>#<occurrence>`.
+    let mut anonymous: HashMap<String, Vec<(usize, String)>> = HashMap::new();
+    let mut globals = Vec::with_capacity(items.len());
+    let mut global = String::new();
+
+    for (index, (_, item)) in items.iter_mut().enumerate() {
+        if let Item::Label(label) = item {
+            if Label::is_local(&label.name) {
+                label.name = format!("{}{}", global, label.name);
+            } else if Label::is_internal(&label.name) {
+                // Generated labels do not open a new scope.
+            } else if Label::is_anonymous(&label.name) {
+                let occurrences = anonymous.entry(label.name.clone()).or_default();
+                let name = format!("{}#{}", label.name, occurrences.len());
+                occurrences.push((index, name.clone()));
+                label.name = name;
+            } else {
+                global = label.name.clone();
+            }
+        }
+        globals.push(global.clone());
+    }
+
+    for (index, (_, item)) in items.iter_mut().enumerate() {
+        if let Item::Instruction(instruction) = item {
+            for address in instruction.addresses_mut() {
+                if let Address::Symbolic(name) | Address::RelativeSymbolic(name) = address {
+                    if let Some(scoped) = scoped_name(name, index, &globals[index], &anonymous) {
+                        *name = scoped;
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn scoped_name(
+    name: &str,
+    index: usize,
+    global: &str,
+    anonymous: &HashMap<String, Vec<(usize, String)>>,
+) -> Option<String> {
+    if Label::is_local(name) {
+        return Some(format!("{}{}", global, name));
+    }
+
+    if let Some(number) = name.strip_suffix('b') {
+        anonymous
+            .get(number)?
+            .iter()
+            .rev()
+            .find(|(definition, _)| *definition < index)
+    } else {
+        anonymous
+            .get(name.strip_suffix('f')?)?
+            .iter()
+            .find(|(definition, _)| *definition > index)
+    }
+    .map(|(_, name)| name.clone())
+}
+
+/// Real instructions of a pseudo instruction, followed by the label of the
+/// address after them when the expansion refers to it. An expanded line
+/// that cannot be parsed fails the whole pseudo instruction.
+fn expand(line: usize, pseudo: &PseudoInstruction) -> Result<Vec<(usize, Item)>, SyntaxError> {
+    // Parsed under a placeholder, then renamed to a name sources cannot use.
+    const PLACEHOLDER: &str = "__next__";
+    let next = format!("{}#next", line);
+
+    let mut items = vec![];
+    for source in pseudo.expand(PLACEHOLDER) {
+        let Ok((_, mut instruction)) = parser::parse_line(&source) else {
+            return Err(SyntaxError::InvalidExpansion {
+                pseudo: pseudo.pseudo.name.to_owned(),
+                expanded: source,
+            });
+        };
+        for address in instruction.addresses_mut() {
+            if let Address::Symbolic(name) | Address::RelativeSymbolic(name) = address {
+                if name == PLACEHOLDER {
+                    *name = next.clone();
+                }
+            }
+        }
+        items.push((line, Item::Instruction(instruction)));
+    }
+    if pseudo.uses_next() {
+        items.push((line, Item::Label(Label::new(None, next))));
+    }
+    Ok(items)
+}
+
+/// Jump taken exactly when the given one is not, for the conditions that
+/// have one.
+fn inverse(opname: &str) -> Option<&'static str> {
+    match opname {
+        "JEQ" => Some("JNE"),
+        "JNE" => Some("JEQ"),
+        "JC" => Some("JNC"),
+        "JNC" => Some("JC"),
+        _ => None,
+    }
+}
+
+/// Instructions replacing a relative jump to a symbol by an absolute `B`.
+/// `JMP` becomes the branch alone, a conditional jump is inverted to skip
+/// over the two words of the branch. `JGT`, `JLT` and `JN` have no inverse,
+/// they jump to the branch and a `JMP` skips it otherwise.
+fn promote(instruction: &Instruction) -> Option<Vec<Instruction>> {
+    let Instruction::VI {
+        opname,
+        displacement: Address::RelativeSymbolic(target),
+    } = instruction
+    else {
+        return None;
+    };
+    let jump = |opname: &str, displacement: i16| Instruction::VI {
+        opname: opname.to_owned(),
+        displacement: Address::Relative(displacement),
+    };
+    let branch = Instruction::V {
+        opname: "B".to_owned(),
+        tsd: Operand::NextWord(Address::Symbolic(target.clone())),
+    };
+
+    if opname == "JMP" {
+        return Some(vec![branch]);
+    }
+    Some(match inverse(opname) {
+        Some(inverse) => vec![jump(inverse, 2), branch],
+        None => vec![jump(opname, 1), jump("JMP", 2), branch],
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::isa::{self, Format};
+    use crate::output;
+    use crate::simulator::{Flags, Machine};
+
+    #[test]
+    fn layout() {
+        let program = Program::parse("(0x10) Start\nMOV @Start, R1\nDEC R1\n(0x20) End\nROI\n");
+        let addresses: Vec<(usize, u16)> = program
+            .statements
+            .iter()
+            .map(|statement| (statement.line, statement.address))
+            .collect();
+        assert_eq!(addresses, vec![(2, 0x10), (3, 0x12), (5, 0x20)]);
+        assert_eq!(program.symbols["End"], 0x20);
+    }
+
+    #[test]
+    fn relative_symbol() {
+        let program = Program::parse("(0x10) Loop\nDEC R1\nJNE Loop\n");
+        let jump = &program.statements[1];
+        assert_eq!(jump.instruction.to_binary(jump.address, &program.symbols), vec![0xF2FE]);
+    }
+
+    #[test]
+    fn relax_far_jump() {
+        let mut program = Program::parse("(0x0) Start\nJNE Far\nJMP Start\n(0x200) Far\nROI\n");
+        assert_eq!(program.validate().len(), 1);
+
+        program.relax();
+        assert_eq!(program.validate(), vec![]);
+        let words: Vec<(u16, Vec<u16>)> = program
+            .statements
+            .iter()
+            .map(|statement| {
+                let words = statement.instruction.to_binary(statement.address, &program.symbols);
+                (statement.address, words)
+            })
+            .collect();
+        assert_eq!(
+            words,
+            vec![
+                (0, vec![0xF102]),
+                (1, vec![0xE810, 0x0200]),
+                (3, vec![0xF0FC]),
+                (0x200, vec![0xE600]),
+            ]
+        );
+    }
+
+    /// Label the machine stops after, running `program` with the given flags.
+    fn stops_at(program: &Program, flags: Flags) -> String {
+        let mut machine = Machine::new(&output::segments(program), 0);
+        machine.flags = flags;
+        machine.run(10, |_, _| ()).unwrap();
+        let stop = machine.pc - 1;
+        program
+            .symbols
+            .iter()
+            .find(|(_, address)| **address == stop)
+            .map(|(name, _)| name.clone())
+            .unwrap()
+    }
+
+    #[test]
+    fn relaxed_branch_is_equivalent() {
+        let jumps = isa::OPCODES
+            .values()
+            .filter(|mnemonic| mnemonic.format == Format::VI && mnemonic.opcode != 0);
+        for jump in jumps {
+            let near = Program::parse(&format!("{} Far\nNear: ROI\nFar: ROI\n", jump.name));
+            let mut relaxed = Program::parse(&format!("{} Far\nNear: ROI\n(0x200) Far\nROI\n", jump.name));
+            relaxed.relax();
+            assert_eq!(relaxed.validate(), vec![], "{}", jump.name);
+
+            for bits in 0..16 {
+                let flags = Flags {
+                    zero: bits & 1 != 0,
+                    carry: bits & 2 != 0,
+                    negative: bits & 4 != 0,
+                    overflow: bits & 8 != 0,
+                };
+                assert_eq!(stops_at(&relaxed, flags), stops_at(&near, flags), "{} with {:?}", jump.name, flags);
+            }
+        }
+    }
+
+    #[test]
+    fn relaxed_shapes() {
+        let shape = |jump: &str| {
+            let mut program = Program::parse(&format!("{} Far\n(0x200) Far\nROI\n", jump));
+            program.relax();
+            program.statements[..program.statements.len() - 1]
+                .iter()
+                .map(|statement| statement.instruction.to_string())
+                .collect::<Vec<String>>()
+        };
+        assert_eq!(shape("JMP"), vec!["B @Far"]);
+        assert_eq!(shape("JNE"), vec!["JEQ 2", "B @Far"]);
+        assert_eq!(shape("JC"), vec!["JNC 2", "B @Far"]);
+        assert_eq!(shape("JGT"), vec!["JGT 1", "JMP 2", "B @Far"]);
+    }
+
+    #[test]
+    fn relax_converges() {
+        // Promoting the first jump pushes the second one out of range.
+        let mut source = String::from("(0x0) Start\nJMP Far\nJEQ Start\n");
+        source.push_str(&"DEC R1\n".repeat(125));
+        source.push_str("JNE Start\n(0x100) Far\nROI\n");
+        let mut program = Program::parse(&source);
+        assert_eq!(program.validate().len(), 1);
+
+        program.relax();
+        assert_eq!(program.validate(), vec![]);
+        let promoted: Vec<usize> = program
+            .statements
+            .iter()
+            .filter(|statement| matches!(statement.instruction, Instruction::V { .. }))
+            .map(|statement| statement.line)
+            .collect();
+        assert_eq!(promoted, vec![2, 129]);
+    }
+
+    #[test]
+    fn anonymous_labels() {
+        let program = Program::parse(
+            "(0x10) Start\n1: DEC R1\nJNE 1b\nJMP 1f\nMOV @1f, R1\n1:\nJMP 1b\n",
+        );
+        let words: Vec<Vec<u16>> = program
+            .statements
+            .iter()
+            .map(|statement| statement.instruction.to_binary(statement.address, &program.symbols))
+            .collect();
+        assert_eq!(
+            words,
+            vec![
+                vec![0xE201],
+                vec![0xF2FE],
+                vec![0xF002],
+                vec![0x7401, 0x0015],
+                vec![0xF0FF],
+            ]
+        );
+    }
+
+    #[test]
+    fn identifiers() {
+        let program = Program::parse("print_char: DEC R1\nJNE print_char\nB @uart.tx\nuart.tx: ROI\n");
+        assert_eq!(program.validate(), vec![]);
+        assert_eq!(program.symbols["uart.tx"], 4);
+    }
+
+    #[test]
+    fn reserved_label() {
+        let program = Program::parse("(0x10) R2\nMOV: ROI\n");
+        assert_eq!(
+            program.validate(),
+            vec![
+                (1, ValidationError::Reserved("R2".to_owned())),
+                (2, ValidationError::Reserved("MOV".to_owned()))
+            ]
+        );
+    }
+
+    #[test]
+    fn undefined_symbol() {
+        let program = Program::parse("Table: ROI\nMOV @Tabel, R1\nJNE Tabel\nJMP 1f\n");
+        assert_eq!(
+            program.check_symbols(),
+            vec![
+                (1, SymbolError::Unused("Table".to_owned())),
+                (
+                    2,
+                    SymbolError::Undefined {
+                        name: "Tabel".to_owned(),
+                        suggestion: Some("Table".to_owned())
+                    }
+                ),
+                (
+                    3,
+                    SymbolError::Undefined {
+                        name: "Tabel".to_owned(),
+                        suggestion: Some("Table".to_owned())
+                    }
+                ),
+                (
+                    4,
+                    SymbolError::Undefined {
+                        name: "1f".to_owned(),
+                        suggestion: None
+                    }
+                ),
+            ]
+        );
+        // The extension word is kept so the following addresses stay right.
+        assert_eq!(program.statements[1].instruction.to_binary(1, &program.symbols).len(), 2);
+    }
+
+    #[test]
+    fn duplicate_and_unused() {
+        let program = Program::parse("Start: ROI\n1: ROI\n1: JMP 1b\n(0x10) Start\n");
+        assert_eq!(
+            program.check_symbols(),
+            vec![
+                (1, SymbolError::Unused("Start".to_owned())),
+                (2, SymbolError::Unused("1#0".to_owned())),
+                (
+                    4,
+                    SymbolError::Duplicate {
+                        name: "Start".to_owned(),
+                        first: 1
+                    }
+                ),
+            ]
+        );
+        assert_eq!(
+            program.diagnostics()[1],
+            Diagnostic::warning(2, "label 1 is never used")
+        );
+    }
+
+    #[test]
+    fn overlap() {
+        let program = Program::parse("(0x0) Init\nLI R0, 0xA\nLI R1, 0xB\n(0x1) Loop\nJMP Loop\n");
+        assert_eq!(
+            program.check_placement(),
+            vec![(
+                5,
+                PlacementError::Overlap {
+                    other: 2,
+                    start: 1,
+                    end: 2
+                }
+            )]
+        );
+        assert_eq!(
+            program.diagnostics().last(),
+            Some(&Diagnostic::error(5, "word 0x0001 is also placed by line 2"))
+        );
+    }
+
+    #[test]
+    fn past_end_of_memory() {
+        let mut program = Program::parse("(0xFFF) End\nMOV @End, R1\n");
+        assert_eq!(program.check_placement(), vec![]);
+        program.memory_size = 0x1000;
+        assert_eq!(
+            program.check_placement(),
+            vec![(
+                2,
+                PlacementError::PastEnd {
+                    end: 0x1001,
+                    memory_size: 0x1000
+                }
+            )]
+        );
+    }
+
+    #[test]
+    fn local_labels() {
+        let program = Program::parse(
+            "(0x10) First\n.loop: DEC R1\nJNE .loop\n(0x20) Second\n.loop: JMP .loop\nB @.loop\n",
+        );
+        assert_eq!(program.symbols["First.loop"], 0x10);
+        assert_eq!(program.symbols["Second.loop"], 0x20);
+        assert_eq!(
+            program.statements[3].instruction,
+            Instruction::V {
+                opname: "B".to_owned(),
+                tsd: Operand::NextWord(Address::Symbolic("Second.loop".to_owned()))
+            }
+        );
+        let jump = &program.statements[1];
+        assert_eq!(jump.instruction.to_binary(jump.address, &program.symbols), vec![0xF2FE]);
+    }
+
+    #[test]
+    fn pseudo_instructions() {
+        let program = Program::parse(
+            "(0x10) Start\nCALL print\nNOP\nLDA R2, Start\nprint: RET\n",
+        );
+        assert_eq!(program.diagnostics(), vec![]);
+        let listing: Vec<(usize, u16, String)> = program
+            .statements
+            .iter()
+            .map(|statement| (statement.line, statement.address, statement.instruction.to_string()))
+            .collect();
+        assert_eq!(
+            listing,
+            vec![
+                (2, 0x10, "LI R15, @2#next".to_owned()),
+                (2, 0x12, "PUSH R15".to_owned()),
+                (2, 0x13, "B @print".to_owned()),
+                (3, 0x15, "JMP 0".to_owned()),
+                (4, 0x16, "LI R2, @Start".to_owned()),
+                (5, 0x18, "PULL R15".to_owned()),
+                (5, 0x19, "B *R15".to_owned()),
+            ]
+        );
+        assert_eq!(program.symbols["2#next"], 0x15);
+    }
+
+    #[test]
+    fn pseudo_keeps_scope() {
+        let program = Program::parse("First: CALL First\n.loop: BRA .loop\n");
+        assert_eq!(program.symbols["First.loop"], 5);
+    }
+
+    #[test]
+    fn pseudo_operands() {
+        let program = Program::parse("Start: CALL 0x100\nCALL *R1\nLDA R1, 0x20\nLDA R2, R3\nBRA Start\n");
+        assert_eq!(
+            program.diagnostics(),
+            vec![
+                Diagnostic::error(1, "invalid operands for CALL, expected label"),
+                Diagnostic::error(2, "invalid operands for CALL, expected label"),
+                Diagnostic::error(3, "invalid operands for LDA, expected tsd, label"),
+                Diagnostic::error(4, "invalid operands for LDA, expected tsd, label"),
+            ]
+        );
+        assert_eq!(program.statements.len(), 1);
+    }
+
+    #[test]
+    fn invalid_expansion() {
+        let pseudo = PseudoInstruction {
+            pseudo: isa::lookup_pseudo("LDA").unwrap(),
+            operands: vec!["R1".to_owned(), "0x20".to_owned()],
+        };
+        assert_eq!(
+            expand(1, &pseudo).map(|items| items.len()).map_err(|error| error.to_string()),
+            Err("LDA expands to LI R1, @0x20, which is not a valid instruction".to_owned())
+        );
+    }
+
+    #[test]
+    fn branch_by_range() {
+        let program = Program::parse("BRA Near\nBRA Far\nNear: ROI\n(0x200) Far\nROI\n");
+        assert_eq!(program.diagnostics(), vec![]);
+        let opnames: Vec<String> = program
+            .statements
+            .iter()
+            .map(|statement| statement.instruction.to_string())
+            .collect();
+        assert_eq!(opnames, vec!["JMP Near", "B @Far", "ROI", "ROI"]);
+        // Explicit relative jumps are only promoted on request.
+        let program = Program::parse("(0x0) Start\nJMP Far\n(0x200) Far\nROI\n");
+        assert_eq!(program.validate().len(), 1);
+    }
+
+    fn memory_map() -> MemoryMap {
+        MemoryMap::parse(
+            "region ROM 0x0000 0x0010 rx\nregion RAM 0x1000 0x0004 rw\nsection .text ROM\nsection .data RAM\nsymbol Afficheur 0x1F00\n",
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn sections_in_regions() {
+        let mut program = Program::parse(
+            "Start: MOV @Counter, @Afficheur\n.data\nCounter: CLR R1\n.text\nB @Start\n",
+        );
+        program.set_memory_map(memory_map());
+        let addresses: Vec<u16> = program.statements.iter().map(|statement| statement.address).collect();
+        assert_eq!(addresses, vec![0x0000, 0x1000, 0x0003]);
+        assert_eq!(program.symbols["Counter"], 0x1000);
+        assert_eq!(
+            program.statements[0].instruction.to_binary(0, &program.symbols),
+            vec![0x7410, 0x1000, 0x1F00]
+        );
+        assert_eq!(
+            program.check_memory(),
+            vec![(
+                3,
+                MemoryError::NotExecutable {
+                    section: ".data".to_owned(),
+                    region: "RAM".to_owned()
+                }
+            )]
+        );
+    }
+
+    #[test]
+    fn region_overflow() {
+        let mut program = Program::parse(".data\nMOV 0x1, 0x2\nMOV 0x1, 0x2\n.section .bss\nROI\n");
+        program.set_memory_map(memory_map());
+        assert_eq!(
+            program
+                .check_memory()
+                .into_iter()
+                .filter(|(_, error)| error.severity() == Severity::Error)
+                .collect::<Vec<_>>(),
+            vec![
+                (
+                    3,
+                    MemoryError::Overflow {
+                        section: ".data".to_owned(),
+                        region: "RAM".to_owned(),
+                        end: 0x1004
+                    }
+                ),
+                (5, MemoryError::Unmapped(".bss".to_owned())),
+            ]
+        );
+    }
+
+    #[test]
+    fn predefined_label() {
+        let mut program = Program::parse("Afficheur: ROI\nB @Afficheur\n");
+        program.set_memory_map(memory_map());
+        assert_eq!(
+            program.check_symbols(),
+            vec![(1, SymbolError::Predefined("Afficheur".to_owned()))]
+        );
+    }
+
+    #[test]
+    fn section_after_pinned_code() {
+        let program = Program::parse("(0x0) Start\nLI R0, 1\nB @Start\n.data\nData: DEC R0\nB @Data\n");
+        assert_eq!(program.symbols["Data"], 0);
+        let overlaps: Vec<usize> = program
+            .check_placement()
+            .into_iter()
+            .filter(|(_, error)| matches!(error, PlacementError::Overlap { .. }))
+            .map(|(line, _)| line)
+            .collect();
+        assert_eq!(overlaps, vec![5, 6, 6]);
+    }
+
+    #[test]
+    fn section_location_counters() {
+        let source = ".section .rodata\nTable: ROI\n.text\nB @Table\n.section .rodata\nEnd: ROI\n";
+        let program = Program::parse(source);
+        assert_eq!((program.symbols["Table"], program.symbols["End"]), (2, 3));
+
+        let mut program = Program::parse(source);
+        program.set_memory_map(
+            MemoryMap::parse("region ROM 0x100 0x100 rx\nsection .text ROM\nsection .rodata ROM\n")
+                .unwrap(),
+        );
+        assert_eq!((program.symbols["Table"], program.symbols["End"]), (0x102, 0x103));
+        assert_eq!(program.check_memory(), vec![]);
+    }
+
+    #[test]
+    fn defines() {
+        let mut program = Program::parse("LI R1, @BAUD\n");
+        program.define("BAUD", 9600);
+        assert_eq!(program.diagnostics(), vec![]);
+        assert_eq!(program.statements[0].instruction.to_binary(0, &program.symbols)[1], 9600);
+
+        let mut program = Program::parse("BAUD: LI R1, @BAUD\n");
+        program.define("BAUD", 9600);
+        assert_eq!(
+            program.diagnostics(),
+            vec![Diagnostic::error(1, "label BAUD is already defined on the command line")]
+        );
+    }
+
+    #[test]
+    fn globals() {
+        let program = Program::parse(".global Start\n.global Stat\nStart: ROI\n");
+        assert_eq!(program.globals, vec![(1, "Start".to_owned()), (2, "Stat".to_owned())]);
+        assert_eq!(
+            program.diagnostics(),
+            vec![Diagnostic::error(2, "undefined symbol Stat, did you mean Start?")]
+        );
+    }
+
+    #[test]
+    fn unknown_instruction() {
+        let program = Program::parse("Start: BEQX @Start\nBEQ @Start\n");
+        assert_eq!(
+            program.diagnostics(),
+            vec![Diagnostic::error(1, "unknown instruction BEQX")]
+        );
+        assert_eq!(program.statements.len(), 1);
+    }
+
+    #[test]
+    fn invalid_operands() {
+        let program = Program::parse("Start: MOV R1\nADD R1, R2 garbage\nB @Start\n");
+        assert_eq!(
+            program.diagnostics(),
+            vec![
+                Diagnostic::error(1, "invalid operands for MOV, expected ts, tsd"),
+                Diagnostic::error(2, "invalid operands for ADD, expected ts, tsd"),
+            ]
+        );
+        assert_eq!(program.statements.len(), 1);
+    }
+
+    #[test]
+    fn number_too_large() {
+        let program = Program::parse("LI R0, 0x1FFFFFFFF\nLI R1, 99999999999\n");
+        assert_eq!(
+            program.diagnostics(),
+            vec![
+                Diagnostic::error(1, "number too large"),
+                Diagnostic::error(2, "number too large"),
+            ]
+        );
+        assert!(program.statements.is_empty());
+    }
+
+    #[test]
+    fn comments() {
+        let program = Program::parse("; Count down\nLoop: DEC R0 ; R0 -= 1\n  JNE Loop;again\n");
+        assert_eq!(program.diagnostics(), vec![]);
+        assert_eq!(program.statements.len(), 2);
+    }
+
+    #[test]
+    fn timing_regions() {
+        let program = Program::parse(".timing_begin loop
+Loop: DEC R0
+JNE Loop
+.timing_end
+.timing_end
+.timing_begin
+");
+        assert_eq!(
+            program.timing,
+            vec![TimingRegion {
+                name: Some("loop".to_owned()),
+                begin: 1,
+                end: 4
+            }]
+        );
+        assert_eq!(
+            program.diagnostics(),
+            vec![
+                Diagnostic::error(5, ".timing_end without .timing_begin"),
+                Diagnostic::error(6, "timing region is never ended"),
+            ]
+        );
+    }
+}
@@ -0,0 +1,337 @@
+use std::fmt;
+
+use crate::diagnostic::Diagnostic;
+use crate::disassembler;
+use crate::parser::{instruction::Instruction, operand::Operand};
+use crate::simulator::{Fault, Machine};
+
+/// Column of a line of the RTL log.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Column {
+    Pc,
+    /// Destination register, `-` when none is written.
+    Register,
+    Value,
+    Ignored,
+}
+
+/// Order of the columns of the RTL log, such as `pc,register,value`.
+#[derive(Debug, PartialEq)]
+pub struct Columns(Vec<Column>);
+
+impl Columns {
+    pub fn parse(spec: &str) -> Result<Columns, String> {
+        let columns = spec
+            .split(',')
+            .map(|name| match name.trim() {
+                "pc" => Ok(Column::Pc),
+                "register" => Ok(Column::Register),
+                "value" => Ok(Column::Value),
+                "_" => Ok(Column::Ignored),
+                name => Err(format!("unknown column {}, expected pc, register, value or _", name)),
+            })
+            .collect::<Result<Vec<Column>, String>>()?;
+
+        let count = |column| columns.iter().filter(|other| **other == column).count();
+        if count(Column::Pc) != 1 {
+            return Err("the columns need a single pc".to_owned());
+        }
+        if count(Column::Register) > 1 || count(Column::Register) != count(Column::Value) {
+            return Err("the columns need both a register and a value, or neither".to_owned());
+        }
+        Ok(Columns(columns))
+    }
+}
+
+/// Instruction retired by the RTL, with the register it wrote.
+#[derive(Debug, PartialEq)]
+pub struct Retired {
+    /// Line of the log, counted from 1.
+    pub line: usize,
+    pub pc: u16,
+    pub write: Option<(u8, u16)>,
+}
+
+fn parse_word(field: &str) -> Option<u16> {
+    let digits = field
+        .strip_prefix("0x")
+        .or_else(|| field.strip_prefix("0X"))
+        .unwrap_or(field);
+    u16::from_str_radix(digits, 16).ok()
+}
+
+/// Register number of a field, `None` for a field telling no register is
+/// written.
+fn parse_register(field: &str) -> Result<Option<u8>, ()> {
+    if field.chars().all(|chr| matches!(chr, '-' | 'x' | 'X')) {
+        return Ok(None);
+    }
+    let digits = field.strip_prefix(['R', 'r']).unwrap_or(field);
+    match digits.parse() {
+        Ok(register) if register < 16 => Ok(Some(register)),
+        _ => Err(()),
+    }
+}
+
+/// Read the instructions retired by the RTL, one per line in hexadecimal.
+/// Fields are separated by spaces or commas, blank lines and those starting
+/// with `#` or `//` are skipped.
+pub fn parse_log(text: &str, columns: &Columns) -> Result<Vec<Retired>, Diagnostic> {
+    let mut retired = vec![];
+    for (number, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') || line.starts_with("//") {
+            continue;
+        }
+        let error = |message: String| Diagnostic::error(number + 1, message);
+        let fields: Vec<&str> = line
+            .split(|chr: char| chr.is_whitespace() || chr == ',')
+            .filter(|field| !field.is_empty())
+            .collect();
+        if fields.len() < columns.0.len() {
+            return Err(error(format!(
+                "expected {} columns, found {}",
+                columns.0.len(),
+                fields.len()
+            )));
+        }
+
+        let (mut pc, mut register, mut value) = (0, None, None);
+        for (column, field) in columns.0.iter().zip(&fields) {
+            match column {
+                Column::Pc => {
+                    pc = parse_word(field).ok_or_else(|| error(format!("invalid pc {}", field)))?
+                }
+                Column::Register => {
+                    register =
+                        parse_register(field).map_err(|_| error(format!("invalid register {}", field)))?
+                }
+                Column::Value => value = parse_word(field),
+                Column::Ignored => (),
+            }
+        }
+        let write = match (register, value) {
+            (Some(register), Some(value)) => Some((register, value)),
+            (Some(_), None) => return Err(error("invalid value".to_owned())),
+            (None, _) => None,
+        };
+        retired.push(Retired {
+            line: number + 1,
+            pc,
+            write,
+        });
+    }
+    Ok(retired)
+}
+
+/// Difference between the RTL and the simulator.
+#[derive(Debug, PartialEq)]
+pub enum Mismatch {
+    Pc { expected: u16 },
+    Write {
+        expected: Option<(u8, u16)>,
+        simulated: Option<(u8, u16)>,
+    },
+    Fault(Fault),
+}
+
+impl fmt::Display for Mismatch {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let write = |write: &Option<(u8, u16)>| match write {
+            Some((register, value)) => format!("R{} = 0x{:04X}", register, value),
+            None => "no register".to_owned(),
+        };
+        match self {
+            Mismatch::Pc { expected } => write!(f, "the RTL retired the instruction at 0x{:04X}", expected),
+            Mismatch::Write {
+                expected,
+                simulated,
+            } => write!(f, "the RTL wrote {}, the simulator {}", write(expected), write(simulated)),
+            Mismatch::Fault(fault) => write!(f, "the simulator stopped: {}", fault),
+        }
+    }
+}
+
+/// First instruction retired differently by the RTL and the simulator.
+#[derive(Debug, PartialEq)]
+pub struct Divergence {
+    /// Number of instructions that matched before it.
+    pub index: usize,
+    /// Line of the log.
+    pub line: usize,
+    /// Address of the instruction run by the simulator.
+    pub address: u16,
+    pub instruction: Option<Instruction>,
+    pub mismatch: Mismatch,
+    /// Registers as written in the log and as simulated, once the instruction
+    /// retired.
+    pub rtl: [u16; 16],
+    pub simulated: [u16; 16],
+}
+
+impl Divergence {
+    /// Registers holding different values, with their RTL and simulated
+    /// values.
+    pub fn registers(&self) -> Vec<(usize, u16, u16)> {
+        (0..16)
+            .filter(|register| self.rtl[*register] != self.simulated[*register])
+            .map(|register| (register, self.rtl[register], self.simulated[register]))
+            .collect()
+    }
+}
+
+/// Register written by an instruction, apart from pointer increments.
+fn destination(instruction: &Instruction) -> Option<u8> {
+    let mnemonic = instruction.mnemonic();
+    let tsd = match instruction {
+        Instruction::I { tsd, .. }
+        | Instruction::II { tsd, .. }
+        | Instruction::Iii { tsd, .. }
+        | Instruction::IV { tsd, .. } => tsd,
+        Instruction::V { .. } | Instruction::VI { .. } => return None,
+    };
+    match tsd {
+        Operand::Direct(register) if mnemonic.writes_destination() && mnemonic.name != "LIMI" => {
+            u8::try_from(register.0).ok()
+        }
+        _ => None,
+    }
+}
+
+/// Run the machine along the log of the RTL, returns the number of
+/// instructions compared or the first one differing. Registers are taken to
+/// start cleared on both sides.
+pub fn compare(machine: &mut Machine, log: &[Retired]) -> Result<usize, Box<Divergence>> {
+    let mut rtl = machine.registers;
+    for (index, retired) in log.iter().enumerate() {
+        let address = machine.pc;
+        let decoded = || {
+            let words: Vec<u16> = (0..3)
+                .map(|offset| machine.memory[usize::from(address.wrapping_add(offset))])
+                .collect();
+            disassembler::decode(&words).map(|(instruction, _)| instruction)
+        };
+        let divergence = |mismatch, instruction, rtl, simulated| {
+            Box::new(Divergence {
+                index,
+                line: retired.line,
+                address,
+                instruction,
+                mismatch,
+                rtl,
+                simulated,
+            })
+        };
+
+        if retired.pc != address {
+            return Err(divergence(
+                Mismatch::Pc { expected: retired.pc },
+                decoded(),
+                rtl,
+                machine.registers,
+            ));
+        }
+        let step = match machine.step() {
+            Ok(step) => step,
+            Err(fault) => return Err(divergence(Mismatch::Fault(fault), None, rtl, machine.registers)),
+        };
+        if let Some((register, value)) = retired.write {
+            rtl[usize::from(register)] = value;
+        }
+        let simulated = destination(&step.instruction)
+            .map(|register| (register, machine.registers[usize::from(register)]));
+        if simulated != retired.write {
+            return Err(divergence(
+                Mismatch::Write {
+                    expected: retired.write,
+                    simulated,
+                },
+                Some(step.instruction),
+                rtl,
+                machine.registers,
+            ));
+        }
+    }
+    Ok(log.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::assembler::Program;
+    use crate::output;
+
+    const SOURCE: &str = "LI R0, 2\nLoop: DEC R0\nJNE Loop\nADDI R1, 5\nROI\n";
+
+    fn machine() -> Machine {
+        Machine::new(&output::segments(&Program::parse(SOURCE)), 0)
+    }
+
+    fn log(text: &str) -> Vec<Retired> {
+        parse_log(text, &Columns::parse("pc,register,value").unwrap()).unwrap()
+    }
+
+    #[test]
+    fn columns() {
+        assert_eq!(
+            Columns::parse("_, pc,value ,register"),
+            Ok(Columns(vec![Column::Ignored, Column::Pc, Column::Value, Column::Register]))
+        );
+        assert!(Columns::parse("pc,register").is_err());
+        assert!(Columns::parse("pc,pc").is_err());
+        assert!(Columns::parse("pc,flags").is_err());
+    }
+
+    #[test]
+    fn log_lines() {
+        let columns = Columns::parse("_,pc,register,value").unwrap();
+        assert_eq!(
+            parse_log("# time pc rd value\n10 0x0000 R0 0002\n\n20, 0002, -, x\n", &columns),
+            Ok(vec![
+                Retired {
+                    line: 2,
+                    pc: 0,
+                    write: Some((0, 2))
+                },
+                Retired {
+                    line: 4,
+                    pc: 2,
+                    write: None
+                },
+            ])
+        );
+        assert_eq!(
+            parse_log("10 0000 R16 0\n", &columns),
+            Err(Diagnostic::error(1, "invalid register R16"))
+        );
+        assert_eq!(
+            parse_log("10 0000\n", &columns),
+            Err(Diagnostic::error(1, "expected 4 columns, found 2"))
+        );
+    }
+
+    #[test]
+    fn matching() {
+        let log = log("0000 r0 0002\n0002 r0 0001\n0003 - -\n0002 r0 0000\n0003 - -\n0004 r1 0005\n");
+        assert_eq!(compare(&mut machine(), &log), Ok(6));
+    }
+
+    #[test]
+    fn divergences() {
+        let divergence = compare(&mut machine(), &log("0000 r0 0002\n0002 r0 0000\n")).unwrap_err();
+        assert_eq!(divergence.index, 1);
+        assert_eq!(divergence.line, 2);
+        assert_eq!(divergence.address, 2);
+        assert_eq!(divergence.instruction.as_ref().unwrap().to_string(), "DEC R0");
+        assert_eq!(
+            divergence.mismatch.to_string(),
+            "the RTL wrote R0 = 0x0000, the simulator R0 = 0x0001"
+        );
+        assert_eq!(divergence.registers(), vec![(0, 0, 1)]);
+
+        let divergence = compare(&mut machine(), &log("0000 r0 0002\n0002 r0 0001\n0004 - -\n")).unwrap_err();
+        assert_eq!(divergence.mismatch, Mismatch::Pc { expected: 4 });
+        assert_eq!(divergence.address, 3);
+        assert_eq!(divergence.instruction.as_ref().unwrap().to_string(), "JNE -2");
+    }
+}
@@ -0,0 +1,42 @@
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Severity::Error => write!(f, "error"),
+            Severity::Warning => write!(f, "warning"),
+        }
+    }
+}
+
+/// Message attached to a source line.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub line: usize,
+    pub severity: Severity,
+    pub message: String,
+}
+
+impl Diagnostic {
+    pub fn error(line: usize, message: impl fmt::Display) -> Self {
+        Diagnostic {
+            line,
+            severity: Severity::Error,
+            message: message.to_string(),
+        }
+    }
+
+    pub fn warning(line: usize, message: impl fmt::Display) -> Self {
+        Diagnostic {
+            line,
+            severity: Severity::Warning,
+            message: message.to_string(),
+        }
+    }
+}
@@ -0,0 +1,106 @@
+use bit_field::BitField;
+
+use crate::isa::{self, Format};
+use crate::parser::{
+    address::Address, immediate::Immediate, instruction::Instruction, operand::Operand,
+    register::Register,
+};
+
+/// Fields that must be cleared in a well formed first word.
+fn reserved(format: Format) -> Option<std::ops::RangeInclusive<usize>> {
+    match format {
+        Format::II => Some(10..=10),
+        Format::Iii => Some(6..=8),
+        Format::IV | Format::V => Some(6..=7),
+        Format::I | Format::VI => None,
+    }
+}
+
+fn operand(
+    mode: u16,
+    register: u16,
+    extension: &mut impl Iterator<Item = u16>,
+) -> Option<Operand> {
+    let register = Register(register.into());
+    Some(match mode {
+        0b00 => Operand::Direct(register),
+        0b01 => Operand::NextWord(Address::Raw(extension.next()?)),
+        0b10 => Operand::Address(register),
+        _ => Operand::AddressIncrement(register),
+    })
+}
+
+/// Decode the instruction starting at the first word, returns it along with
+/// the number of words it spans.
+pub fn decode(words: &[u16]) -> Option<(Instruction, usize)> {
+    let (&first, rest) = words.split_first()?;
+    let mnemonic = isa::decode(first)?;
+    if let Some(bits) = reserved(mnemonic.format) {
+        if first.get_bits(bits) != 0 {
+            return None;
+        }
+    }
+
+    let mut extension = rest.iter().copied();
+    let opname = mnemonic.name.to_owned();
+    let instruction = match mnemonic.format {
+        Format::I => Instruction::I {
+            opname,
+            ts: operand(first.get_bits(10..=11), first.get_bits(6..=9), &mut extension)?,
+            tsd: operand(first.get_bits(4..=5), first.get_bits(..=3), &mut extension)?,
+        },
+        Format::II => Instruction::II {
+            opname,
+            shift: first.get_bits(6..=9).into(),
+            tsd: operand(first.get_bits(4..=5), first.get_bits(..=3), &mut extension)?,
+        },
+        Format::Iii => Instruction::Iii {
+            opname,
+            immediate: Immediate::Value(extension.next()?.into()),
+            tsd: operand(first.get_bits(4..=5), first.get_bits(..=3), &mut extension)?,
+        },
+        Format::IV => Instruction::IV {
+            opname,
+            tsd: operand(first.get_bits(4..=5), first.get_bits(..=3), &mut extension)?,
+        },
+        Format::V => Instruction::V {
+            opname,
+            tsd: operand(first.get_bits(4..=5), first.get_bits(..=3), &mut extension)?,
+        },
+        Format::VI => Instruction::VI {
+            opname,
+            displacement: Address::Relative((first.get_bits(..=7) as u8 as i8).into()),
+        },
+    };
+
+    Some((instruction, words.len() - extension.count()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn truncated_extension() {
+        assert_eq!(decode(&[0x7C50]), None)
+    }
+
+    #[test]
+    fn reserved_bits() {
+        assert_eq!(decode(&[0xE240]), None)
+    }
+
+    #[test]
+    fn relative() {
+        assert_eq!(
+            decode(&[0xF2FD, 0x1234]),
+            Some((
+                Instruction::VI {
+                    opname: "JNE".to_owned(),
+                    displacement: Address::Relative(-3)
+                },
+                1
+            ))
+        )
+    }
+}
@@ -0,0 +1,251 @@
+use crate::isa;
+use crate::parser::{self, identifier::is_identifier_char};
+
+/// Narrowest label column, instructions start past it.
+const INDENT: usize = 8;
+
+/// Source line split into the parts written in their own column.
+#[derive(Debug, PartialEq)]
+enum Line<'a> {
+    Blank,
+    /// Comment on its own line, indented ones are aligned with instructions.
+    Comment { indented: bool, text: &'a str },
+    Code {
+        label: Option<String>,
+        /// Mnemonic and operands written canonically.
+        statement: Option<(String, String)>,
+        comment: Option<&'a str>,
+    },
+    /// Code that cannot be parsed entirely, such as data words, kept as
+    /// written but moved to the instruction column.
+    Verbatim {
+        label: Option<String>,
+        code: &'a str,
+        comment: Option<&'a str>,
+    },
+}
+
+/// Write the leading word in upper case when it names an instruction.
+fn upper_mnemonic(code: &str) -> String {
+    let end = code
+        .find(|chr| !is_identifier_char(chr))
+        .unwrap_or(code.len());
+    let (word, rest) = code.split_at(end);
+    let upper = word.to_ascii_uppercase();
+    if isa::lookup(&upper).is_some() || isa::lookup_pseudo(&upper).is_some() {
+        upper + rest
+    } else {
+        code.to_owned()
+    }
+}
+
+/// Directive, pseudo instruction or instruction split into its mnemonic
+/// and its operands.
+fn statement(code: &str) -> Option<(String, String)> {
+    let code = upper_mnemonic(code);
+    let text = if let Ok(("", directive)) = parser::parse_directive(&code) {
+        directive.to_string()
+    } else if let Ok(("", pseudo)) = parser::parse_pseudo(&code) {
+        pseudo.to_string()
+    } else if let Ok(("", instruction)) = parser::parse_line(&code) {
+        instruction.to_string()
+    } else {
+        return None;
+    };
+
+    Some(match text.split_once(' ') {
+        Some((mnemonic, operands)) => (mnemonic.to_owned(), operands.to_owned()),
+        None => (text, String::new()),
+    })
+}
+
+fn split(line: &str) -> Line<'_> {
+    let (code, comment) = parser::split_comment(line);
+    let comment = comment.map(str::trim_end);
+    if code.trim().is_empty() {
+        return match comment {
+            Some(text) => Line::Comment {
+                indented: !code.is_empty(),
+                text,
+            },
+            None => Line::Blank,
+        };
+    }
+
+    let (rest, label) = match parser::parse_label(code) {
+        Ok((rest, label)) => (rest.trim(), Some(label.to_string())),
+        Err(_) => (code.trim(), None),
+    };
+    let statement = match rest {
+        "" => None,
+        rest => match statement(rest) {
+            Some(statement) => Some(statement),
+            None => {
+                return Line::Verbatim {
+                    label,
+                    code: rest,
+                    comment,
+                }
+            }
+        },
+    };
+    Line::Code {
+        label,
+        statement,
+        comment,
+    }
+}
+
+fn width(text: &str) -> usize {
+    text.chars().count()
+}
+
+/// Re-emit a source with labels, mnemonics, operands and comments aligned
+/// in columns, numbers and mnemonics written canonically. Blank lines are
+/// kept, code that cannot be parsed entirely is only moved to the
+/// instruction column.
+pub fn format(source: &str) -> String {
+    let lines: Vec<Line> = source.lines().map(split).collect();
+
+    // Labels on their own line do not push the instructions.
+    let mut label_width = INDENT;
+    let mut mnemonic_width = 0;
+    for line in &lines {
+        match line {
+            Line::Code {
+                label,
+                statement: Some((mnemonic, _)),
+                ..
+            } => {
+                let label = label.as_deref().map_or(0, width);
+                label_width = label_width.max(label + 1);
+                mnemonic_width = mnemonic_width.max(width(mnemonic) + 1);
+            }
+            Line::Verbatim { label, .. } => {
+                label_width = label_width.max(label.as_deref().map_or(0, width) + 1);
+            }
+            _ => (),
+        }
+    }
+
+    let verbatim = |label: &Option<String>, code: &str| {
+        format!(
+            "{:<label_width$}{}",
+            label.as_deref().unwrap_or_default(),
+            code,
+            label_width = label_width
+        )
+    };
+    let code = |label: &Option<String>, statement: &Option<(String, String)>| {
+        let label = label.as_deref().unwrap_or_default();
+        match statement {
+            Some((mnemonic, operands)) => format!(
+                "{:<label_width$}{:<mnemonic_width$}{}",
+                label,
+                mnemonic,
+                operands,
+                label_width = label_width,
+                mnemonic_width = mnemonic_width
+            )
+            .trim_end()
+            .to_owned(),
+            None => label.to_owned(),
+        }
+    };
+
+    let comment_column = lines
+        .iter()
+        .filter_map(|line| match line {
+            Line::Code {
+                label,
+                statement,
+                comment: Some(_),
+            } => Some(width(&code(label, statement)) + 1),
+            Line::Verbatim {
+                label,
+                code,
+                comment: Some(_),
+            } => Some(width(&verbatim(label, code)) + 1),
+            _ => None,
+        })
+        .max()
+        .unwrap_or(0);
+
+    let commented = |code: String, comment: &Option<&str>| match comment {
+        Some(comment) => format!("{:<column$}{}", code, comment, column = comment_column),
+        None => code,
+    };
+
+    let mut result = String::new();
+    for line in &lines {
+        match line {
+            Line::Blank => (),
+            Line::Comment { indented, text } => {
+                let indent = if *indented { label_width } else { 0 };
+                result.push_str(&format!("{:indent$}{}", "", text, indent = indent));
+            }
+            Line::Code {
+                label,
+                statement,
+                comment,
+            } => result.push_str(&commented(code(label, statement), comment)),
+            Line::Verbatim {
+                label,
+                code,
+                comment,
+            } => result.push_str(&commented(verbatim(label, code), comment)),
+        }
+        result.push('\n');
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn canonical() {
+        assert_eq!(
+            format("(0x0) Init\nli\tR0,\t\t0xA\n\nLoop: DEC   R0\nJNE\t-0x1\n  call Loop\n"),
+            "(0x0000) Init\n        LI   R0, 0x000A\n\nLoop:   DEC  R0\n        JNE  -1\n        CALL Loop\n"
+        );
+    }
+
+    #[test]
+    fn columns() {
+        assert_eq!(
+            format("; Count down\nCountdown: LI R0, 10 ; start\n.loop: DEC R0\n   ; again\nJNE .loop;until zero\n\t0x0048\n"),
+            concat!(
+                "; Count down\n",
+                "Countdown: LI  R0, 0x000A ; start\n",
+                ".loop:     DEC R0\n",
+                "           ; again\n",
+                "           JNE .loop      ;until zero\n",
+                "           0x0048\n",
+            )
+        );
+    }
+
+    #[test]
+    fn verbatim() {
+        assert_eq!(
+            format("(0x1022) Table\n    0x0048\n\t0x0065 ; e\nStart: mov r1,r2\n"),
+            concat!(
+                "(0x1022) Table\n",
+                "        0x0048\n",
+                "        0x0065 ; e\n",
+                "Start:  mov r1,r2\n",
+            )
+        );
+        let formatted = format(include_str!("../res/test.asm"));
+        assert!(formatted.contains("(0x1022) Table\n        0x0048\n        0x0065\n        0x006C\n"));
+    }
+
+    #[test]
+    fn idempotent() {
+        let source = include_str!("../res/test.asm");
+        let formatted = format(source);
+        assert_eq!(format(&formatted), formatted);
+    }
+}
@@ -0,0 +1,209 @@
+use std::collections::{BTreeSet, HashMap};
+use std::ops::Range;
+
+use crate::assembler::Program;
+use crate::parser::{
+    instruction::Instruction, label::Label, operand::Operand, register::Register,
+};
+
+/// Register holding the return address of the `CALL` expansion.
+const LINK: Operand = Operand::Direct(Register(15));
+
+/// Name of the code at an address, the first label there in alphabetical
+/// order, or the address itself.
+fn name(program: &Program, address: u16) -> String {
+    program
+        .symbols
+        .iter()
+        .filter(|(name, value)| **value == address && !Label::is_internal(name))
+        .map(|(name, _)| name)
+        .min()
+        .cloned()
+        .unwrap_or_else(|| format!("0x{:04X}", address))
+}
+
+/// Text of a DOT string, without its quotes.
+fn escape(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+/// Subroutine called by the `CALL` expansion ending with statement `index`,
+/// and the address it returns to.
+fn call(program: &Program, index: usize) -> Option<(u16, u16)> {
+    let [link, push, branch] = program.statements.get(index.checked_sub(2)?..=index)? else {
+        return None;
+    };
+    match (&link.instruction, &push.instruction, &branch.instruction) {
+        (
+            Instruction::Iii {
+                opname: li,
+                immediate,
+                tsd,
+            },
+            Instruction::IV { opname: pushed, tsd: saved },
+            Instruction::V { opname: b, .. },
+        ) if li == "LI" && pushed == "PUSH" && b == "B" && *tsd == LINK && *saved == LINK => Some((
+            branch.target(&program.symbols)?,
+            immediate.resolve(&program.symbols)?,
+        )),
+        _ => None,
+    }
+}
+
+/// Split the statements into basic blocks, entered at their first statement
+/// and left at their last one. Blocks start at labels, sections and jump
+/// targets, and after jumps.
+fn blocks(program: &Program) -> Vec<Range<usize>> {
+    let statements = &program.statements;
+    let targets: BTreeSet<u16> = statements
+        .iter()
+        .filter_map(|statement| statement.target(&program.symbols))
+        .collect();
+
+    let mut starts: Vec<usize> = (0..statements.len())
+        .filter(|index| {
+            program.starts_block(*index)
+                || targets.contains(&statements[*index].address)
+                || statements[index - 1].instruction.mnemonic().is_jump()
+        })
+        .collect();
+    starts.push(statements.len());
+    starts.windows(2).map(|pair| pair[0]..pair[1]).collect()
+}
+
+/// Control flow graph of a program in the DOT language of Graphviz. Each
+/// block lists its instructions, jumps lead to their target and, when they
+/// are conditional, to the next block. Subroutine calls lead to the block
+/// they return to.
+pub fn control_flow(program: &Program) -> String {
+    let statements = &program.statements;
+    let blocks = blocks(program);
+    let starts: HashMap<u16, usize> = blocks
+        .iter()
+        .enumerate()
+        .map(|(index, block)| (statements[block.start].address, index))
+        .collect();
+
+    let mut result = String::from("digraph control_flow {\n    node [shape=box, fontname=monospace];\n");
+    for (index, block) in blocks.iter().enumerate() {
+        let mut label = format!("{}\\l", escape(&name(program, statements[block.start].address)));
+        for statement in &statements[block.clone()] {
+            label.push_str(&format!(
+                "{:04X}  {}\\l",
+                statement.address,
+                escape(&statement.instruction.to_string())
+            ));
+        }
+        result.push_str(&format!("    b{} [label=\"{}\"];\n", index, label));
+    }
+
+    for (index, block) in blocks.iter().enumerate() {
+        let last = &statements[block.end - 1];
+        let next = last.address.wrapping_add(last.instruction.size());
+        let mnemonic = last.instruction.mnemonic();
+        let mut edge = |address: u16, attributes: &str| {
+            if let Some(target) = starts.get(&address) {
+                result.push_str(&format!("    b{} -> b{}{};\n", index, target, attributes));
+            }
+        };
+
+        if let Some((callee, back)) = call(program, block.end - 1) {
+            let attributes = format!(" [label=\"call {}\", style=dashed]", escape(&name(program, callee)));
+            edge(back, &attributes);
+        } else if mnemonic.is_conditional() {
+            if let Some(target) = last.target(&program.symbols) {
+                edge(target, " [label=\"taken\"]");
+            }
+            edge(next, " [label=\"not taken\"]");
+        } else if mnemonic.is_jump() {
+            if let Some(target) = last.target(&program.symbols) {
+                edge(target, "");
+            }
+        } else {
+            edge(next, "");
+        }
+    }
+    result.push_str("}\n");
+    result
+}
+
+/// Graph of the subroutine calls of a program in the DOT language of
+/// Graphviz. Routines run from a global label to the next one, calls are
+/// recognized from the expansion of `CALL`.
+pub fn call_graph(program: &Program) -> String {
+    let routines: Vec<(usize, &str)> = program
+        .labels()
+        .filter(|(_, label)| !Label::is_internal(&label.name) && !label.name.contains('.'))
+        .map(|(line, label)| (line, label.name.as_str()))
+        .collect();
+    let routine = |line: usize| {
+        routines
+            .iter()
+            .take_while(|(start, _)| *start <= line)
+            .last()
+            .map(|(_, name)| *name)
+    };
+
+    // Labels of data have no statements and are left out.
+    let nodes: BTreeSet<&str> = program
+        .statements
+        .iter()
+        .filter_map(|statement| routine(statement.line))
+        .collect();
+    let calls: BTreeSet<(&str, String)> = (0..program.statements.len())
+        .filter_map(|index| {
+            let (callee, _) = call(program, index)?;
+            Some((routine(program.statements[index].line)?, name(program, callee)))
+        })
+        .collect();
+
+    let mut result = String::from("digraph calls {\n");
+    for node in &nodes {
+        result.push_str(&format!("    \"{}\";\n", escape(node)));
+    }
+    for (caller, callee) in &calls {
+        result.push_str(&format!("    \"{}\" -> \"{}\";\n", escape(caller), escape(callee)));
+    }
+    result.push_str("}\n");
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SOURCE: &str = "Main: LI R0, 3\n.loop: CALL Tick\nDEC R0\nJNE .loop\nBRA Main\nTick: CALL Beep\nRET\nBeep: RET\n";
+
+    #[test]
+    fn basic_blocks() {
+        let program = Program::parse(SOURCE);
+        assert_eq!(program.diagnostics(), vec![]);
+        assert_eq!(blocks(&program), vec![0..1, 1..4, 4..6, 6..7, 7..10, 10..12, 12..14]);
+    }
+
+    #[test]
+    fn control_flow_edges() {
+        let graph = control_flow(&Program::parse(SOURCE));
+        let edges: Vec<&str> = graph.lines().filter(|line| line.contains("->")).collect();
+        assert_eq!(
+            edges,
+            vec![
+                "    b0 -> b1;",
+                "    b1 -> b2 [label=\"call Tick\", style=dashed];",
+                "    b2 -> b1 [label=\"taken\"];",
+                "    b2 -> b3 [label=\"not taken\"];",
+                "    b3 -> b0;",
+                "    b4 -> b5 [label=\"call Beep\", style=dashed];",
+            ]
+        );
+        assert!(graph.contains("b0 [label=\"Main\\l0000  LI R0, 0x0003\\l\"]"));
+    }
+
+    #[test]
+    fn calls() {
+        assert_eq!(
+            call_graph(&Program::parse(SOURCE)),
+            "digraph calls {\n    \"Beep\";\n    \"Main\";\n    \"Tick\";\n    \"Main\" -> \"Tick\";\n    \"Tick\" -> \"Beep\";\n}\n"
+        );
+    }
+}
@@ -0,0 +1,476 @@
+use std::ops::RangeInclusive;
+
+use bit_field::BitField;
+use phf::phf_ordered_map;
+
+/// Instruction formats of the APAS isa.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    I,
+    II,
+    Iii,
+    IV,
+    V,
+    VI,
+}
+
+impl Format {
+    pub const ALL: [Format; 6] = [
+        Format::I,
+        Format::II,
+        Format::Iii,
+        Format::IV,
+        Format::V,
+        Format::VI,
+    ];
+
+    /// Fixed bits identifying the format and their value.
+    pub fn prefix(&self) -> (RangeInclusive<usize>, u16) {
+        match self {
+            Format::I => (15..=15, 0b0),
+            Format::II => (14..=15, 0b10),
+            Format::Iii => (13..=15, 0b110),
+            Format::IV => (11..=15, 0b11100),
+            Format::V => (11..=15, 0b11101),
+            Format::VI => (11..=15, 0b11110),
+        }
+    }
+
+    /// Bits holding the opcode within the first word.
+    pub fn opcode_field(&self) -> RangeInclusive<usize> {
+        match self {
+            Format::I => 12..=14,
+            Format::II => 11..=13,
+            Format::Iii => 9..=12,
+            Format::IV | Format::V | Format::VI => 8..=10,
+        }
+    }
+
+    /// Name and width of each field of the first word, most significant
+    /// first.
+    pub fn fields(&self) -> &'static [(&'static str, usize)] {
+        match self {
+            Format::I => &[
+                ("format", 1),
+                ("opcode", 3),
+                ("ts mode", 2),
+                ("ts", 4),
+                ("tsd mode", 2),
+                ("tsd", 4),
+            ],
+            Format::II => &[
+                ("format", 2),
+                ("opcode", 3),
+                ("unused", 1),
+                ("shift", 4),
+                ("tsd mode", 2),
+                ("tsd", 4),
+            ],
+            Format::Iii => &[
+                ("format", 3),
+                ("opcode", 4),
+                ("unused", 3),
+                ("tsd mode", 2),
+                ("tsd", 4),
+            ],
+            Format::IV | Format::V => &[
+                ("format", 5),
+                ("opcode", 3),
+                ("unused", 2),
+                ("tsd mode", 2),
+                ("tsd", 4),
+            ],
+            Format::VI => &[("format", 5), ("opcode", 3), ("displacement", 8)],
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Format::I => "I",
+            Format::II => "II",
+            Format::Iii => "III",
+            Format::IV => "IV",
+            Format::V => "V",
+            Format::VI => "VI",
+        }
+    }
+
+    /// Find the format of an encoded first word.
+    pub fn of(word: u16) -> Option<Format> {
+        Format::ALL.into_iter().find(|format| {
+            let (bits, value) = format.prefix();
+            word.get_bits(bits) == value
+        })
+    }
+}
+
+/// Kind of an operand as written in the source.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OperandKind {
+    /// Source operand, any addressing mode.
+    Source,
+    /// Source and destination operand, any addressing mode.
+    Destination,
+    /// Decimal shift count.
+    Shift,
+    /// 16 bits immediate value stored in an extension word.
+    Immediate,
+    /// 8 bits relative displacement.
+    Displacement,
+    /// Symbol, only taken by pseudo instructions.
+    Label,
+}
+
+impl OperandKind {
+    pub fn name(&self) -> &'static str {
+        match self {
+            OperandKind::Source => "ts",
+            OperandKind::Destination => "tsd",
+            OperandKind::Shift => "shift",
+            OperandKind::Immediate => "immediate",
+            OperandKind::Displacement => "displacement",
+            OperandKind::Label => "label",
+        }
+    }
+}
+
+/// Description of a single mnemonic.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Mnemonic {
+    pub name: &'static str,
+    pub format: Format,
+    pub opcode: u16,
+    pub operands: &'static [OperandKind],
+    /// Cycles taken with register operands, see `MODES` for the others.
+    pub cycles: u32,
+}
+
+/// Addressing mode of a source or destination operand.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Mode {
+    pub name: &'static str,
+    pub syntax: &'static str,
+    /// Cycles added to the instruction.
+    pub cycles: u32,
+}
+
+/// Addressing modes, indexed by their encoding. A next word operand costs
+/// the fetch of its extension word and the memory access. Like those of
+/// `OPCODES`, the cycles are provisional estimates.
+pub static MODES: [Mode; 4] = [
+    Mode {
+        name: "register",
+        syntax: "R1",
+        cycles: 0,
+    },
+    Mode {
+        name: "next word",
+        syntax: "@Label",
+        cycles: 2,
+    },
+    Mode {
+        name: "pointer",
+        syntax: "*R1",
+        cycles: 1,
+    },
+    Mode {
+        name: "incremented pointer",
+        syntax: "*R1+",
+        cycles: 1,
+    },
+];
+
+impl Mnemonic {
+    /// Whether the instruction updates the condition flags.
+    pub fn sets_flags(&self) -> bool {
+        match self.format {
+            Format::I => !matches!(self.name, "STR" | "MOV"),
+            Format::II => true,
+            Format::Iii => !matches!(self.name, "STRI" | "LI" | "LIMI"),
+            Format::IV => !matches!(self.name, "PUSH" | "PULL" | "ROI"),
+            Format::V | Format::VI => false,
+        }
+    }
+
+    /// Whether the instruction writes to its destination operand.
+    pub fn writes_destination(&self) -> bool {
+        match self.format {
+            Format::I => self.name != "CMP",
+            Format::II => true,
+            Format::Iii => self.name != "CI",
+            Format::IV => !matches!(self.name, "PUSH" | "ROI" | "TST"),
+            Format::V | Format::VI => false,
+        }
+    }
+
+    /// Whether the instruction is a jump or a branch, `B` and `JMP` being
+    /// the unconditional ones.
+    pub fn is_jump(&self) -> bool {
+        matches!(self.format, Format::V | Format::VI)
+    }
+
+    pub fn is_conditional(&self) -> bool {
+        self.is_jump() && self.opcode != 0
+    }
+}
+
+use OperandKind::*;
+
+const TS_TSD: &[OperandKind] = &[Source, Destination];
+const SHIFT_TSD: &[OperandKind] = &[Shift, Destination];
+const TSD_IMMEDIATE: &[OperandKind] = &[Destination, Immediate];
+const TSD: &[OperandKind] = &[Destination];
+const DISPLACEMENT: &[OperandKind] = &[Displacement];
+
+macro_rules! mnemonic {
+    ($name:literal, $format:ident, $opcode:literal, $operands:expr, $cycles:literal) => {
+        Mnemonic {
+            name: $name,
+            format: Format::$format,
+            opcode: $opcode,
+            operands: $operands,
+            cycles: $cycles,
+        }
+    };
+}
+
+/// The APAS instruction set, in documentation order.
+///
+/// The cycle counts are provisional estimates: the CPU documentation gives
+/// no timing, they are to be replaced by those of the RTL once measured.
+pub static OPCODES: phf::OrderedMap<&'static str, Mnemonic> = phf_ordered_map! {
+    // I
+    "OR" => mnemonic!("OR", I, 0b000, TS_TSD, 2),
+    "AND" => mnemonic!("AND", I, 0b001, TS_TSD, 2),
+    "XOR" => mnemonic!("XOR", I, 0b010, TS_TSD, 2),
+    "CMP" => mnemonic!("CMP", I, 0b011, TS_TSD, 2),
+    "ADD" => mnemonic!("ADD", I, 0b100, TS_TSD, 2),
+    "STR" => mnemonic!("STR", I, 0b101, TS_TSD, 2),
+    "MUL" => mnemonic!("MUL", I, 0b110, TS_TSD, 4),
+    "MOV" => mnemonic!("MOV", I, 0b111, TS_TSD, 2),
+    // II
+    "SLL" => mnemonic!("SLL", II, 0b000, SHIFT_TSD, 2),
+    "SRL" => mnemonic!("SRL", II, 0b001, SHIFT_TSD, 2),
+    "SLA" => mnemonic!("SLA", II, 0b010, SHIFT_TSD, 2),
+    "SRA" => mnemonic!("SRA", II, 0b011, SHIFT_TSD, 2),
+    "ROT" => mnemonic!("ROT", II, 0b100, SHIFT_TSD, 2),
+    // III
+    "ORI" => mnemonic!("ORI", Iii, 0b0000, TSD_IMMEDIATE, 3),
+    "ANDI" => mnemonic!("ANDI", Iii, 0b0001, TSD_IMMEDIATE, 3),
+    "XORI" => mnemonic!("XORI", Iii, 0b0010, TSD_IMMEDIATE, 3),
+    "CI" => mnemonic!("CI", Iii, 0b0011, TSD_IMMEDIATE, 3),
+    "ADDI" => mnemonic!("ADDI", Iii, 0b0100, TSD_IMMEDIATE, 3),
+    "STRI" => mnemonic!("STRI", Iii, 0b0101, TSD_IMMEDIATE, 3),
+    "MULI" => mnemonic!("MULI", Iii, 0b0110, TSD_IMMEDIATE, 5),
+    "LI" => mnemonic!("LI", Iii, 0b1000, TSD_IMMEDIATE, 3),
+    "LIMI" => mnemonic!("LIMI", Iii, 0b1001, TSD_IMMEDIATE, 3),
+    // IV
+    "NOT" => mnemonic!("NOT", IV, 0b000, TSD, 2),
+    "INC" => mnemonic!("INC", IV, 0b001, TSD, 2),
+    "DEC" => mnemonic!("DEC", IV, 0b010, TSD, 2),
+    "CLR" => mnemonic!("CLR", IV, 0b011, TSD, 2),
+    "PUSH" => mnemonic!("PUSH", IV, 0b100, TSD, 3),
+    "PULL" => mnemonic!("PULL", IV, 0b101, TSD, 3),
+    "ROI" => mnemonic!("ROI", IV, 0b110, &[], 4),
+    "TST" => mnemonic!("TST", IV, 0b111, TSD, 2),
+    // SET shares its encoding with TST, a decoded word is always shown as TST.
+    "SET" => mnemonic!("SET", IV, 0b111, TSD, 2),
+    // V
+    "B" => mnemonic!("B", V, 0b000, TSD, 2),
+    "BEQ" => mnemonic!("BEQ", V, 0b001, TSD, 2),
+    "BNE" => mnemonic!("BNE", V, 0b010, TSD, 2),
+    "BC" => mnemonic!("BC", V, 0b011, TSD, 2),
+    "BNC" => mnemonic!("BNC", V, 0b100, TSD, 2),
+    "BGT" => mnemonic!("BGT", V, 0b101, TSD, 2),
+    "BLT" => mnemonic!("BLT", V, 0b110, TSD, 2),
+    "BN" => mnemonic!("BN", V, 0b111, TSD, 2),
+    // VI
+    "JMP" => mnemonic!("JMP", VI, 0b000, DISPLACEMENT, 2),
+    "JEQ" => mnemonic!("JEQ", VI, 0b001, DISPLACEMENT, 2),
+    "JNE" => mnemonic!("JNE", VI, 0b010, DISPLACEMENT, 2),
+    "JC" => mnemonic!("JC", VI, 0b011, DISPLACEMENT, 2),
+    "JNC" => mnemonic!("JNC", VI, 0b100, DISPLACEMENT, 2),
+    "JGT" => mnemonic!("JGT", VI, 0b101, DISPLACEMENT, 2),
+    "JLT" => mnemonic!("JLT", VI, 0b110, DISPLACEMENT, 2),
+    "JN" => mnemonic!("JN", VI, 0b111, DISPLACEMENT, 2),
+};
+
+/// Pseudo instruction expanding to a sequence of real instructions.
+///
+/// In the expansion `{0}`, `{1}`... stand for the operands as written and
+/// `{next}` for the address following the expansion.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Pseudo {
+    pub name: &'static str,
+    pub operands: &'static [OperandKind],
+    pub expansion: &'static [&'static str],
+    /// Relative jumps of the expansion are promoted to absolute branches
+    /// when their target is out of range.
+    pub relax: bool,
+}
+
+/// Pseudo instructions, subroutines use R15 as the link register.
+pub static PSEUDO: phf::OrderedMap<&'static str, Pseudo> = phf_ordered_map! {
+    "NOP" => Pseudo {
+        name: "NOP",
+        operands: &[],
+        expansion: &["JMP 0"],
+        relax: false,
+    },
+    "CALL" => Pseudo {
+        name: "CALL",
+        operands: &[Label],
+        expansion: &["LI R15, @{next}", "PUSH R15", "B @{0}"],
+        relax: false,
+    },
+    "RET" => Pseudo {
+        name: "RET",
+        operands: &[],
+        expansion: &["PULL R15", "B *R15"],
+        relax: false,
+    },
+    "BRA" => Pseudo {
+        name: "BRA",
+        operands: &[Label],
+        expansion: &["JMP {0}"],
+        relax: true,
+    },
+    "LDA" => Pseudo {
+        name: "LDA",
+        operands: &[Destination, Label],
+        expansion: &["LI {0}, @{1}"],
+        relax: false,
+    },
+};
+
+pub fn lookup(name: &str) -> Option<&'static Mnemonic> {
+    OPCODES.get(name)
+}
+
+/// Find the mnemonic encoded in a first instruction word.
+pub fn decode(word: u16) -> Option<&'static Mnemonic> {
+    let format = Format::of(word)?;
+    let opcode = word.get_bits(format.opcode_field());
+    OPCODES
+        .values()
+        .find(|mnemonic| mnemonic.format == format && mnemonic.opcode == opcode)
+}
+
+pub fn lookup_pseudo(name: &str) -> Option<&'static Pseudo> {
+    PSEUDO.get(name)
+}
+
+/// Warning printed with the pseudo instructions.
+const CLOBBERED: &str = "CALL overwrites R15 with the return address";
+
+/// Warning printed with the cycle counts until they are measured on the RTL.
+const PROVISIONAL: &str = "cycle counts are provisional estimates, not measured on the CPU";
+
+/// Render the instruction set as a markdown table.
+pub fn document() -> String {
+    let mut result = format!("> Note: {}.\n\n", PROVISIONAL);
+    result.push_str("| Mnemonic | Format | Opcode | Operands | Cycles |\n");
+    result.push_str("|----------|--------|--------|----------|--------|\n");
+    for mnemonic in OPCODES.values() {
+        let width = mnemonic.format.opcode_field().count();
+        let operands = mnemonic
+            .operands
+            .iter()
+            .map(OperandKind::name)
+            .collect::<Vec<_>>()
+            .join(", ");
+        result.push_str(&format!(
+            "| {} | {} | {:0width$b} | {} | {} |\n",
+            mnemonic.name,
+            mnemonic.format.name(),
+            mnemonic.opcode,
+            operands,
+            mnemonic.cycles,
+            width = width
+        ));
+    }
+
+    result.push_str("\n| Mode | Syntax | Extra cycles |\n");
+    result.push_str("|------|--------|--------------|\n");
+    for mode in &MODES {
+        result.push_str(&format!("| {} | `{}` | {} |\n", mode.name, mode.syntax, mode.cycles));
+    }
+
+    result.push_str("\n| Pseudo | Operands | Expansion |\n");
+    result.push_str("|--------|----------|-----------|\n");
+    for pseudo in PSEUDO.values() {
+        let operands = pseudo
+            .operands
+            .iter()
+            .map(OperandKind::name)
+            .collect::<Vec<_>>()
+            .join(", ");
+        result.push_str(&format!(
+            "| {} | {} | `{}` |\n",
+            pseudo.name,
+            operands,
+            pseudo.expansion.join("; ")
+        ));
+    }
+    result.push_str(&format!("\n> Note: {}.\n", CLOBBERED));
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keys_match_names() {
+        for (key, mnemonic) in OPCODES.entries() {
+            assert_eq!(*key, mnemonic.name)
+        }
+    }
+
+    #[test]
+    fn opcodes_fit_their_field() {
+        for mnemonic in OPCODES.values() {
+            let width = mnemonic.format.opcode_field().count();
+            assert!(mnemonic.opcode < 1 << width, "{}", mnemonic.name)
+        }
+    }
+
+    #[test]
+    fn opcodes_are_unique() {
+        for (index, mnemonic) in OPCODES.values().enumerate() {
+            for other in OPCODES.values().skip(index + 1) {
+                assert!(
+                    mnemonic.format != other.format
+                        || mnemonic.opcode != other.opcode
+                        || (mnemonic.name, other.name) == ("TST", "SET"),
+                    "{} and {} share an encoding",
+                    mnemonic.name,
+                    other.name
+                )
+            }
+        }
+    }
+
+    #[test]
+    fn fields_cover_the_word() {
+        for format in Format::ALL {
+            let fields = format.fields();
+            assert_eq!(fields.iter().map(|(_, width)| width).sum::<usize>(), 16);
+            assert_eq!(fields[0].1, format.prefix().0.count());
+            assert_eq!(fields[1].1, format.opcode_field().count());
+        }
+    }
+
+    #[test]
+    fn pseudo_do_not_shadow_mnemonics() {
+        for (key, pseudo) in PSEUDO.entries() {
+            assert_eq!(*key, pseudo.name);
+            assert!(lookup(key).is_none(), "{}", key)
+        }
+    }
+
+    #[test]
+    fn readme_is_up_to_date() {
+        assert!(include_str!("../README.md").contains(&document()))
+    }
+}
@@ -0,0 +1,284 @@
+use std::{collections::HashMap, fmt};
+
+use bit_field::BitField;
+
+use crate::object::{Object, RelocationKind};
+use crate::placement::MEMORY_SIZE;
+
+#[derive(Debug, PartialEq)]
+pub enum LinkError {
+    Duplicate { name: String, first: String },
+    Undefined(String),
+    Displacement { name: String, value: i32 },
+    Overlap { other: String, start: u32 },
+    /// Relocation of a word past the end of its section.
+    Outside { section: String, offset: u16 },
+    NoRoom { size: usize },
+}
+
+impl fmt::Display for LinkError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LinkError::Duplicate { name, first } => {
+                write!(f, "symbol {} is already exported by {}", name, first)
+            }
+            LinkError::Undefined(name) => write!(f, "undefined symbol {}", name),
+            LinkError::Displacement { name, value } => write!(
+                f,
+                "jump to {} spans {} words, use an absolute branch",
+                name, value
+            ),
+            LinkError::Overlap { other, start } => {
+                write!(f, "section at 0x{:04X} overlaps a section of {}", start, other)
+            }
+            LinkError::Outside { section, offset } => {
+                write!(f, "relocation at 0x{:04X} is outside of section {}", offset, section)
+            }
+            LinkError::NoRoom { size } => {
+                write!(f, "no room left in memory for a {} words section", size)
+            }
+        }
+    }
+}
+
+/// Words occupied by a placed section, from `start` up to `end` excluded.
+struct Placed {
+    module: usize,
+    start: u32,
+    end: u32,
+}
+
+/// Lowest address from `start` where `size` words do not overlap `placed`.
+fn first_fit(placed: &[Placed], size: u32) -> Option<u32> {
+    let mut start = 0;
+    while let Some(other) = placed
+        .iter()
+        .find(|other| start < other.end && other.start < start + size)
+    {
+        start = other.end;
+    }
+    (start + size <= MEMORY_SIZE).then_some(start)
+}
+
+/// Place the sections of every module, resolve the references between them
+/// and build the memory image starting at address 0.
+///
+/// Errors are reported along with the index of the module they concern.
+pub fn link(modules: &[(String, Object)]) -> Result<Vec<u16>, Vec<(usize, LinkError)>> {
+    let mut errors = vec![];
+
+    // Pinned sections first, relocatable ones fill the gaps in module order.
+    let mut placed: Vec<Placed> = vec![];
+    let mut starts: Vec<Vec<u16>> = modules
+        .iter()
+        .map(|(_, object)| vec![0; object.sections.len()])
+        .collect();
+    for (module, (_, object)) in modules.iter().enumerate() {
+        for (index, section) in object.sections.iter().enumerate() {
+            if let Some(origin) = section.origin {
+                let start = u32::from(origin);
+                let end = start + section.words.len() as u32;
+                if let Some(other) = placed
+                    .iter()
+                    .find(|other| start < other.end && other.start < end)
+                {
+                    let other = modules[other.module].0.clone();
+                    errors.push((module, LinkError::Overlap { other, start }));
+                }
+                placed.push(Placed { module, start, end });
+                starts[module][index] = origin;
+            }
+        }
+    }
+    for (module, (_, object)) in modules.iter().enumerate() {
+        for (index, section) in object.sections.iter().enumerate() {
+            if section.origin.is_some() || section.words.is_empty() {
+                continue;
+            }
+            let size = section.words.len();
+            match first_fit(&placed, size as u32) {
+                Some(start) => {
+                    placed.push(Placed {
+                        module,
+                        start,
+                        end: start + size as u32,
+                    });
+                    starts[module][index] = start as u16;
+                }
+                None => errors.push((module, LinkError::NoRoom { size })),
+            }
+        }
+    }
+
+    let address = |module: usize, section: usize, offset: u16| {
+        starts[module][section].wrapping_add(offset)
+    };
+    let mut exported: HashMap<&str, (usize, u16)> = HashMap::new();
+    for (module, (_, object)) in modules.iter().enumerate() {
+        for symbol in object.symbols.iter().filter(|symbol| symbol.exported) {
+            let value = address(module, symbol.section, symbol.offset);
+            if let Some((first, _)) = exported.insert(&symbol.name, (module, value)) {
+                let first = modules[first].0.clone();
+                let name = symbol.name.clone();
+                errors.push((module, LinkError::Duplicate { name, first }));
+            }
+        }
+    }
+
+    let end = placed.iter().map(|placed| placed.end).max().unwrap_or(0);
+    let mut image = vec![0; end as usize];
+    for (module, (_, object)) in modules.iter().enumerate() {
+        for (index, section) in object.sections.iter().enumerate() {
+            let start = usize::from(starts[module][index]);
+            image[start..start + section.words.len()].copy_from_slice(&section.words);
+        }
+
+        // Symbols of the module itself shadow the exported ones.
+        let resolve = |name: &str| {
+            object
+                .symbols
+                .iter()
+                .find(|symbol| symbol.name == name)
+                .map(|symbol| address(module, symbol.section, symbol.offset))
+                .or_else(|| exported.get(name).map(|(_, value)| *value))
+        };
+        for relocation in &object.relocations {
+            let section = &object.sections[relocation.section];
+            if usize::from(relocation.offset) >= section.words.len() {
+                let section = section.name.clone();
+                errors.push((module, LinkError::Outside { section, offset: relocation.offset }));
+                continue;
+            }
+            let place = address(module, relocation.section, relocation.offset);
+            let Some(target) = resolve(&relocation.symbol) else {
+                let error = (module, LinkError::Undefined(relocation.symbol.clone()));
+                if !errors.contains(&error) {
+                    errors.push(error);
+                }
+                continue;
+            };
+            let word = &mut image[usize::from(place)];
+            match relocation.kind {
+                RelocationKind::Absolute => *word = target,
+                RelocationKind::Relative => {
+                    let value = i32::from(target) - i32::from(place) - 1;
+                    if i8::try_from(value).is_err() {
+                        let name = relocation.symbol.clone();
+                        errors.push((module, LinkError::Displacement { name, value }));
+                    }
+                    word.set_bits(..=7, u16::from(value as u8));
+                }
+            }
+        }
+    }
+
+    if errors.is_empty() {
+        Ok(image)
+    } else {
+        Err(errors)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::assembler::Program;
+
+    fn module(name: &str, source: &str) -> (String, Object) {
+        let mut program = Program::parse(source);
+        program.relocatable = true;
+        (name.to_owned(), Object::from(&program))
+    }
+
+    #[test]
+    fn cross_module() {
+        let image = link(&[
+            module("main", "Start: CALL print\nJMP Start\n"),
+            module("print", ".global print\nprint: DEC R1\nJNE print\nRET\n"),
+        ]);
+        assert_eq!(
+            image,
+            Ok(vec![
+                0xD00F, 0x0005, 0xE40F, 0xE810, 0x0006, 0xF0FA, 0xE201, 0xF2FE, 0xE50F, 0xE82F
+            ])
+        );
+    }
+
+    #[test]
+    fn relocatable_around_pinned() {
+        let image = link(&[
+            module("main", "B @Handler\n"),
+            module("vector", ".global Handler\n(0x1) Handler\nROI\n"),
+        ])
+        .unwrap();
+        assert_eq!(image, vec![0x0000, 0xE600, 0xE810, 0x0001]);
+    }
+
+    #[test]
+    fn local_labels() {
+        let image = link(&[
+            module("a", ".global Main\nMain: CALL Wait\nLoop: JMP Loop\n"),
+            module("b", ".global Wait\nWait: LI R0, 2\nLoop: DEC R0\nJNE Loop\nRET\n"),
+        ]);
+        assert_eq!(
+            image,
+            Ok(vec![
+                0xD00F, 0x0005, 0xE40F, 0xE810, 0x0006, 0xF0FF, 0xD000, 0x0002, 0xE200, 0xF2FE,
+                0xE50F, 0xE82F
+            ])
+        );
+    }
+
+    #[test]
+    fn relocation_outside() {
+        let (name, mut object) = module("a", "B @Start\nStart: ROI\n");
+        object.relocations[0].offset = 0x50;
+        assert_eq!(
+            link(&[(name, object)]),
+            Err(vec![(
+                0,
+                LinkError::Outside {
+                    section: ".text".to_owned(),
+                    offset: 0x50
+                }
+            )])
+        );
+    }
+
+    #[test]
+    fn errors() {
+        let errors = link(&[
+            module("a", ".global Start\nStart: JMP Far\nB @Missing\nB @Missing\n"),
+            module("b", ".global Start\n.global Far\nStart: ROI\n(0x200) Far\nROI\n"),
+            module("c", "(0x1FF) Other\nROI\nROI\n"),
+        ])
+        .unwrap_err();
+        assert_eq!(
+            errors,
+            vec![
+                (
+                    2,
+                    LinkError::Overlap {
+                        other: "b".to_owned(),
+                        start: 0x1FF
+                    }
+                ),
+                (
+                    1,
+                    LinkError::Duplicate {
+                        name: "Start".to_owned(),
+                        first: "a".to_owned()
+                    }
+                ),
+                (
+                    0,
+                    LinkError::Displacement {
+                        name: "Far".to_owned(),
+                        value: 0x1FF
+                    }
+                ),
+                (0, LinkError::Undefined("Missing".to_owned())),
+            ]
+        );
+    }
+}
@@ -0,0 +1,495 @@
+use std::collections::HashSet;
+use std::fmt;
+
+use crate::assembler::Program;
+use crate::diagnostic::Diagnostic;
+use crate::parser::{
+    self, address::Address, instruction::Instruction, label::Label, operand::Operand, register::Register,
+};
+
+/// Check for code that assembles but is likely wrong.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Lint {
+    /// Instruction following an unconditional jump without a label.
+    Unreachable,
+    /// Conditional jump with no flag-setting instruction before it.
+    UnsetFlags,
+    /// Instruction writing over a code label.
+    CodeWrite,
+    /// `LI` with a memory destination.
+    MemoryLoad,
+    /// Routine pushing more or less words than it pulls.
+    StackImbalance,
+    /// Jump to itself without a comment.
+    SelfJump,
+    /// `R15` read after a `CALL` overwrote it.
+    ClobberedLink,
+}
+
+impl Lint {
+    pub const ALL: [Lint; 7] = [
+        Lint::Unreachable,
+        Lint::UnsetFlags,
+        Lint::CodeWrite,
+        Lint::MemoryLoad,
+        Lint::StackImbalance,
+        Lint::SelfJump,
+        Lint::ClobberedLink,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Lint::Unreachable => "unreachable",
+            Lint::UnsetFlags => "unset-flags",
+            Lint::CodeWrite => "code-write",
+            Lint::MemoryLoad => "memory-load",
+            Lint::StackImbalance => "stack-imbalance",
+            Lint::SelfJump => "self-jump",
+            Lint::ClobberedLink => "clobbered-link",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Lint> {
+        Lint::ALL.into_iter().find(|lint| lint.name() == name)
+    }
+}
+
+/// What to do with the findings of a lint.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Level {
+    Allow,
+    Warn,
+    Deny,
+}
+
+/// Level of every lint.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Lints([Level; Lint::ALL.len()]);
+
+impl Lints {
+    pub fn new(level: Level) -> Lints {
+        Lints([level; Lint::ALL.len()])
+    }
+
+    pub fn set(&mut self, lint: Lint, level: Level) {
+        self.0[lint as usize] = level;
+    }
+
+    pub fn level(&self, lint: Lint) -> Level {
+        self.0[lint as usize]
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum LintWarning {
+    Unreachable { jump: usize },
+    UnsetFlags(String),
+    CodeWrite(String),
+    MemoryLoad,
+    StackImbalance {
+        routine: String,
+        pushed: usize,
+        pulled: usize,
+    },
+    SelfJump,
+    ClobberedLink { call: usize },
+}
+
+impl LintWarning {
+    pub fn lint(&self) -> Lint {
+        match self {
+            LintWarning::Unreachable { .. } => Lint::Unreachable,
+            LintWarning::UnsetFlags(_) => Lint::UnsetFlags,
+            LintWarning::CodeWrite(_) => Lint::CodeWrite,
+            LintWarning::MemoryLoad => Lint::MemoryLoad,
+            LintWarning::StackImbalance { .. } => Lint::StackImbalance,
+            LintWarning::SelfJump => Lint::SelfJump,
+            LintWarning::ClobberedLink { .. } => Lint::ClobberedLink,
+        }
+    }
+}
+
+impl fmt::Display for LintWarning {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LintWarning::Unreachable { jump } => write!(
+                f,
+                "instruction is unreachable after the jump on line {}",
+                jump
+            ),
+            LintWarning::UnsetFlags(opname) => write!(
+                f,
+                "{} tests flags no preceding instruction sets",
+                opname
+            ),
+            LintWarning::CodeWrite(name) => write!(
+                f,
+                "instruction writes over code at label {}",
+                Label::source_name(name)
+            ),
+            LintWarning::MemoryLoad => write!(
+                f,
+                "LI stores its value to memory, the destination is usually a register"
+            ),
+            LintWarning::StackImbalance {
+                routine,
+                pushed,
+                pulled,
+            } => write!(
+                f,
+                "routine {} pushes {} words but pulls {}",
+                routine, pushed, pulled
+            ),
+            LintWarning::SelfJump => write!(f, "jump to itself without a comment"),
+            LintWarning::ClobberedLink { call } => write!(
+                f,
+                "R15 is read after the CALL on line {} overwrote it with the return address",
+                call
+            ),
+        }
+    }
+}
+
+fn destination(instruction: &Instruction) -> Option<&Operand> {
+    match instruction {
+        Instruction::I { tsd, .. }
+        | Instruction::II { tsd, .. }
+        | Instruction::Iii { tsd, .. }
+        | Instruction::IV { tsd, .. } => Some(tsd),
+        Instruction::V { .. } | Instruction::VI { .. } => None,
+    }
+}
+
+/// Whether an instruction reads a register, directly or as a pointer.
+fn reads(instruction: &Instruction, register: &Register) -> bool {
+    let read = |operand: &Operand, overwritten: bool| match operand {
+        Operand::Direct(direct) => direct == register && !overwritten,
+        Operand::Address(pointer) | Operand::AddressIncrement(pointer) => pointer == register,
+        Operand::NextWord(_) => false,
+    };
+    let overwritten = overwrites(instruction.opname());
+    match instruction {
+        Instruction::I { ts, tsd, .. } => read(ts, false) || read(tsd, overwritten),
+        Instruction::II { tsd, .. }
+        | Instruction::Iii { tsd, .. }
+        | Instruction::IV { tsd, .. }
+        | Instruction::V { tsd, .. } => instruction.opname() != "ROI" && read(tsd, overwritten),
+        Instruction::VI { .. } => false,
+    }
+}
+
+/// Whether an instruction writes its destination without reading it.
+fn overwrites(opname: &str) -> bool {
+    matches!(opname, "MOV" | "LI" | "LIMI" | "CLR" | "PULL")
+}
+
+/// Source lines, counted from 1, split into label and code.
+struct Lines<'a>(Vec<&'a str>);
+
+impl<'a> Lines<'a> {
+    fn code(&self, line: usize) -> &'a str {
+        let (code, _) = parser::split_comment(self.0.get(line.wrapping_sub(1)).unwrap_or(&""));
+        match parser::parse_label(code) {
+            Ok((rest, _)) => rest,
+            Err(_) => code,
+        }
+    }
+
+    /// Name of the label defined on a line as written in the source.
+    fn label(&self, line: usize) -> Option<String> {
+        let text = self.0.get(line.wrapping_sub(1))?;
+        parser::parse_label(text).ok().map(|(_, label)| label.name)
+    }
+
+    fn is_pseudo(&self, line: usize) -> bool {
+        parser::parse_pseudo(self.code(line)).is_ok()
+    }
+
+    fn is_call(&self, line: usize) -> bool {
+        parser::parse_pseudo(self.code(line)).is_ok_and(|(_, pseudo)| pseudo.pseudo.name == "CALL")
+    }
+
+    /// Whether a line, or the one before it, has a comment.
+    fn is_commented(&self, line: usize) -> bool {
+        let comment = |line: usize| {
+            self.0
+                .get(line.wrapping_sub(1))
+                .is_some_and(|text| parser::split_comment(text).1.is_some())
+        };
+        let alone = |line: usize| {
+            self.0
+                .get(line.wrapping_sub(1))
+                .is_some_and(|text| parser::split_comment(text).0.trim().is_empty())
+        };
+        comment(line) || (comment(line - 1) && alone(line - 1))
+    }
+}
+
+fn unreachable(program: &Program) -> Vec<(usize, LintWarning)> {
+    let statements = &program.statements;
+    (1..statements.len())
+        .filter(|index| !program.starts_block(*index))
+        .filter_map(|index| {
+            let jump = &statements[index - 1];
+            let mnemonic = jump.instruction.mnemonic();
+            // NOP jumps to the next instruction.
+            let next = jump.address.wrapping_add(jump.instruction.size());
+            (mnemonic.is_jump() && !mnemonic.is_conditional() && jump.target(&program.symbols) != Some(next))
+                .then(|| (statements[index].line, LintWarning::Unreachable { jump: jump.line }))
+        })
+        .collect()
+}
+
+fn unset_flags(program: &Program) -> Vec<(usize, LintWarning)> {
+    let statements = &program.statements;
+    let mut warnings = vec![];
+    for (index, statement) in statements.iter().enumerate() {
+        // Flags of a jump target come from wherever the jumps are.
+        if !statement.instruction.mnemonic().is_conditional() || program.starts_block(index) {
+            continue;
+        }
+        let mut previous = index;
+        let set = loop {
+            if program.starts_block(previous) {
+                break false;
+            }
+            previous -= 1;
+            if statements[previous].instruction.mnemonic().sets_flags() {
+                break true;
+            }
+        };
+        if !set {
+            let opname = statement.instruction.opname().to_owned();
+            warnings.push((statement.line, LintWarning::UnsetFlags(opname)));
+        }
+    }
+    warnings
+}
+
+fn code_write(program: &Program) -> Vec<(usize, LintWarning)> {
+    let code: HashSet<u16> = program
+        .statements
+        .iter()
+        .map(|statement| statement.address)
+        .collect();
+    program
+        .statements
+        .iter()
+        .filter(|statement| statement.instruction.mnemonic().writes_destination())
+        .filter_map(|statement| match destination(&statement.instruction)? {
+            Operand::NextWord(Address::Symbolic(name))
+                if program.symbol_sections.contains_key(name)
+                    && !Label::is_internal(name)
+                    && code.contains(&program.symbols[name]) =>
+            {
+                Some((statement.line, LintWarning::CodeWrite(name.clone())))
+            }
+            _ => None,
+        })
+        .collect()
+}
+
+fn memory_load(program: &Program) -> Vec<(usize, LintWarning)> {
+    program
+        .statements
+        .iter()
+        .filter(|statement| {
+            matches!(
+                &statement.instruction,
+                Instruction::Iii { opname, tsd, .. } if opname == "LI" && !matches!(tsd, Operand::Direct(_))
+            )
+        })
+        .map(|statement| (statement.line, LintWarning::MemoryLoad))
+        .collect()
+}
+
+/// Routines run from a global label to the next one, the stack operations
+/// of pseudo instructions such as `CALL` and `RET` balance across routines
+/// and are left out.
+fn stack_imbalance(program: &Program, lines: &Lines) -> Vec<(usize, LintWarning)> {
+    let routines: Vec<(usize, &str)> = program
+        .labels()
+        .filter(|(line, label)| {
+            !Label::is_internal(&label.name)
+                && lines
+                    .label(*line)
+                    .is_some_and(|written| !Label::is_local(&written))
+        })
+        .map(|(line, label)| (line, label.name.as_str()))
+        .collect();
+
+    let mut warnings = vec![];
+    for (index, (line, routine)) in routines.iter().enumerate() {
+        let end = routines.get(index + 1).map_or(usize::MAX, |(next, _)| *next);
+        let mut counts = (0, 0);
+        for statement in &program.statements {
+            if statement.line < *line || statement.line >= end || lines.is_pseudo(statement.line) {
+                continue;
+            }
+            match statement.instruction.opname() {
+                "PUSH" => counts.0 += 1,
+                "PULL" => counts.1 += 1,
+                _ => (),
+            }
+        }
+        if counts.0 != counts.1 {
+            warnings.push((
+                *line,
+                LintWarning::StackImbalance {
+                    routine: routine.to_string(),
+                    pushed: counts.0,
+                    pulled: counts.1,
+                },
+            ));
+        }
+    }
+    warnings
+}
+
+fn self_jump(program: &Program, lines: &Lines) -> Vec<(usize, LintWarning)> {
+    program
+        .statements
+        .iter()
+        .filter(|statement| statement.target(&program.symbols) == Some(statement.address))
+        .filter(|statement| !lines.is_pseudo(statement.line) && !lines.is_commented(statement.line))
+        .map(|statement| (statement.line, LintWarning::SelfJump))
+        .collect()
+}
+
+/// Instructions reading `R15` after a `CALL` returned, up to the next
+/// instruction writing it, pseudo instruction or unconditional jump.
+fn clobbered_link(program: &Program, lines: &Lines) -> Vec<(usize, LintWarning)> {
+    const LINK: Register = Register(15);
+    let statements = &program.statements;
+    let mut warnings = vec![];
+    for (index, call) in statements.iter().enumerate() {
+        // From the last instruction of the expansion.
+        let expanded = statements.get(index + 1).is_some_and(|next| next.line == call.line);
+        if !lines.is_call(call.line) || expanded {
+            continue;
+        }
+        for statement in &statements[index + 1..] {
+            if lines.is_pseudo(statement.line) {
+                break;
+            }
+            let instruction = &statement.instruction;
+            if reads(instruction, &LINK) {
+                warnings.push((statement.line, LintWarning::ClobberedLink { call: call.line }));
+                break;
+            }
+            let mnemonic = instruction.mnemonic();
+            let written =
+                overwrites(instruction.opname()) && destination(instruction) == Some(&Operand::Direct(LINK));
+            if written || (mnemonic.is_jump() && !mnemonic.is_conditional()) {
+                break;
+            }
+        }
+    }
+    warnings
+}
+
+/// Run the lints not allowed over a program assembled from `source`,
+/// denied ones are reported as errors.
+pub fn lint(program: &Program, source: &str, lints: &Lints) -> Vec<Diagnostic> {
+    let lines = Lines(source.lines().collect());
+    let mut warnings: Vec<(usize, LintWarning)> = unreachable(program)
+        .into_iter()
+        .chain(unset_flags(program))
+        .chain(code_write(program))
+        .chain(memory_load(program))
+        .chain(stack_imbalance(program, &lines))
+        .chain(self_jump(program, &lines))
+        .chain(clobbered_link(program, &lines))
+        .collect();
+    warnings.sort_by_key(|(line, _)| *line);
+
+    warnings
+        .into_iter()
+        .filter_map(|(line, warning)| {
+            let message = format!("{} [{}]", warning, warning.lint().name());
+            match lints.level(warning.lint()) {
+                Level::Allow => None,
+                Level::Warn => Some(Diagnostic::warning(line, message)),
+                Level::Deny => Some(Diagnostic::error(line, message)),
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Lines flagged by a single lint.
+    fn flagged(source: &str, lint: Lint) -> Vec<usize> {
+        let mut lints = Lints::new(Level::Allow);
+        lints.set(lint, Level::Warn);
+        lint_lines(source, &lints)
+    }
+
+    fn lint_lines(source: &str, lints: &Lints) -> Vec<usize> {
+        lint(&Program::parse(source), source, lints)
+            .iter()
+            .map(|diagnostic| diagnostic.line)
+            .collect()
+    }
+
+    #[test]
+    fn unreachable() {
+        let source = "JMP End\nDEC R0\nEnd: NOP\nINC R0\nRET\nROI\nCALL End\nROI\n";
+        assert_eq!(flagged(source, Lint::Unreachable), vec![2, 6]);
+    }
+
+    #[test]
+    fn unset_flags() {
+        let source = "Start: MOV R1, R2\nJNE Start\nDEC R0\nMOV R0, R1\nJNE Start\nWait: JEQ Wait\n";
+        assert_eq!(flagged(source, Lint::UnsetFlags), vec![2]);
+    }
+
+    #[test]
+    fn memory_writes() {
+        let source = "Start: MOV R1, @Start\nLI 0x12, 0xA\nLI R0, 0xA\nMOV R0, @Data\nB @Start\n(0x100) Data\n";
+        assert_eq!(flagged(source, Lint::CodeWrite), vec![1]);
+        assert_eq!(flagged(source, Lint::MemoryLoad), vec![2]);
+    }
+
+    #[test]
+    fn stack_imbalance() {
+        let source = "Save: PUSH R1\nPUSH R2\n.done: PULL R2\nRET\nMain: CALL Save\nJMP Main\n";
+        assert_eq!(flagged(source, Lint::StackImbalance), vec![1]);
+    }
+
+    #[test]
+    fn self_jump() {
+        let source = "Stop: JMP -1\nWait: JMP Wait ; until reset\n; halt\nJMP -1\n";
+        assert_eq!(flagged(source, Lint::SelfJump), vec![1]);
+    }
+
+    #[test]
+    fn clobbered_link() {
+        let source = concat!(
+            "Main: LI R15, 0x10\nCALL Sub\nADD R15, R1\n",
+            "LI R15, 0x20\nCALL Sub\nMOV R0, R15\nMOV *R15, R1\n",
+            "CALL Sub\nB *R15\n",
+            "Sub: RET\n",
+        );
+        assert_eq!(flagged(source, Lint::ClobberedLink), vec![3, 9]);
+    }
+
+    #[test]
+    fn levels() {
+        let source = "LI 0x12, 0xA\nJMP -1\n";
+        let mut lints = Lints::new(Level::Warn);
+        lints.set(Lint::SelfJump, Level::Allow);
+        lints.set(Lint::MemoryLoad, Level::Deny);
+        assert_eq!(
+            lint(&Program::parse(source), source, &lints),
+            vec![Diagnostic::error(
+                1,
+                "LI stores its value to memory, the destination is usually a register [memory-load]"
+            )]
+        );
+        assert_eq!(Lint::from_name("self-jump"), Some(Lint::SelfJump));
+        assert!(lint_lines(source, &Lints::new(Level::Allow)).is_empty());
+    }
+}
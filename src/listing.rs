@@ -0,0 +1,142 @@
+use std::collections::HashMap;
+
+use crate::assembler::Program;
+use crate::parser::{self, label::Label};
+use crate::source::Source;
+
+/// Total cycles of the statements laid out from lines `lines`.
+fn cycles(program: &Program, lines: std::ops::Range<usize>) -> u32 {
+    program
+        .statements
+        .iter()
+        .filter(|statement| lines.contains(&statement.line))
+        .map(|statement| statement.instruction.cycles())
+        .sum()
+}
+
+/// Whether a line holds a pseudo instruction or a jump relaxed into several
+/// instructions, listed one by one under the line.
+fn expanded(source: &Source, line: usize, statements: usize) -> bool {
+    let (code, _) = parser::split_comment(source.line(line));
+    let code = match parser::parse_label(code) {
+        Ok((rest, _)) => rest,
+        Err(_) => code,
+    };
+    statements > 1 || parser::parse_pseudo(code.trim()).is_ok()
+}
+
+/// Line number, address, encoded words, cycles and source of every
+/// statement. A pseudo instruction is followed by every instruction it
+/// expands to, marked `+`. The cycles from each label to the next one and
+/// those of every timing region are totalled after the statements.
+pub fn listing(program: &Program, source: &Source) -> String {
+    let mut result = String::new();
+    let mut previous = None;
+    let mut statements: HashMap<usize, usize> = HashMap::new();
+    for statement in &program.statements {
+        *statements.entry(statement.line).or_default() += 1;
+    }
+
+    for statement in &program.statements {
+        let expanded = expanded(source, statement.line, statements[&statement.line]);
+        if expanded && previous != Some(statement.line) {
+            let text = source.line(statement.line).trim();
+            result.push_str(&format!("{:>5}{:29}{}\n", source.origin(statement.line).1, "", text));
+        }
+        let words: Vec<String> = statement
+            .instruction
+            .to_binary(statement.address, &program.symbols)
+            .iter()
+            .map(|word| format!("{:04X}", word))
+            .collect();
+        let text = if expanded {
+            format!("    + {}", statement.instruction)
+        } else {
+            source.line(statement.line).trim().to_owned()
+        };
+        result.push_str(&format!(
+            "{:>5}  {:04X}  {:<14}  {:>3}  {}\n",
+            source.origin(statement.line).1,
+            statement.address,
+            words.join(" "),
+            statement.instruction.cycles(),
+            text
+        ));
+        previous = Some(statement.line);
+    }
+
+    let labels: Vec<(usize, &Label)> = program
+        .labels()
+        .filter(|(_, label)| !Label::is_internal(&label.name))
+        .collect();
+    if !labels.is_empty() {
+        result.push_str("\nCycles by label\n");
+        for (index, (line, label)) in labels.iter().enumerate() {
+            let next = labels.get(index + 1).map_or(usize::MAX, |(next, _)| *next);
+            result.push_str(&format!(
+                "{:>5}  {:<24}  {:>5}\n",
+                source.origin(*line).1,
+                label.name,
+                cycles(program, *line..next)
+            ));
+        }
+    }
+
+    if !program.timing.is_empty() {
+        result.push_str("\nTiming regions\n");
+        for region in &program.timing {
+            let (begin, end) = (source.origin(region.begin).1, source.origin(region.end).1);
+            let name = match &region.name {
+                Some(name) => name.clone(),
+                None => format!("line {}", begin),
+            };
+            result.push_str(&format!(
+                "{:>5}  {:<24}  {:>5}  lines {}-{}\n",
+                begin,
+                name,
+                cycles(program, region.begin + 1..region.end),
+                begin,
+                end
+            ));
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pseudo_expansion() {
+        let text = "Start:\n  LI R0, 0xA\n  RET\n";
+        let source = Source::load("main.asm", text, &[], &|_| None).unwrap();
+        assert_eq!(
+            listing(&Program::parse(&source.text), &source),
+            concat!(
+                "    2  0000  D000 000A         3  LI R0, 0xA\n",
+                "    3                             RET\n",
+                "    3  0002  E50F              3      + PULL R15\n",
+                "    3  0003  E82F              3      + B *R15\n",
+                "\n",
+                "Cycles by label\n",
+                "    1  Start                         9\n",
+            )
+        );
+    }
+
+    #[test]
+    fn timing() {
+        let text = "Init: LI R0, 3\n.timing_begin loop\nLoop: DEC R0\n  JNE Loop\n.timing_end\n";
+        let source = Source::load("main.asm", text, &[], &|_| None).unwrap();
+        let listing = listing(&Program::parse(&source.text), &source);
+        assert!(listing.ends_with(concat!(
+            "Cycles by label\n",
+            "    1  Init                          3\n",
+            "    3  Loop                          4\n",
+            "\n",
+            "Timing regions\n",
+            "    2  loop                          4  lines 2-5\n",
+        )));
+    }
+}
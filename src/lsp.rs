@@ -0,0 +1,624 @@
+use std::collections::HashMap;
+use std::io::{self, BufRead, Write};
+use std::path::Path;
+
+use bit_field::BitField;
+use serde_json::{json, Value};
+
+use crate::assembler::{Program, Statement};
+use crate::diagnostic::{Diagnostic, Severity};
+use crate::isa::{Format, OperandKind, OPCODES, PSEUDO};
+use crate::parser::{address::Address, identifier::is_identifier_char, label::Label};
+use crate::source::Source;
+use crate::validation::REGISTERS;
+
+/// JSON-RPC error returned for the requests the server does not handle.
+const METHOD_NOT_FOUND: i64 = -32601;
+
+/// Constants of the protocol.
+const DIAGNOSTIC_ERROR: u8 = 1;
+const DIAGNOSTIC_WARNING: u8 = 2;
+const COMPLETION_VARIABLE: u8 = 6;
+const COMPLETION_KEYWORD: u8 = 14;
+const SYMBOL_FUNCTION: u8 = 12;
+const SYNC_FULL: u8 = 1;
+
+/// Source opened in the editor along with the program assembled from it.
+struct Document {
+    uri: String,
+    source: Source,
+    program: Program,
+    /// Error met while expanding the includes, the program is then
+    /// assembled from the file alone.
+    include_error: Option<Diagnostic>,
+}
+
+/// Path of a `file://` URI.
+fn path(uri: &str) -> String {
+    let encoded = uri.strip_prefix("file://").unwrap_or(uri).as_bytes();
+    let mut decoded = Vec::with_capacity(encoded.len());
+    let mut index = 0;
+    while index < encoded.len() {
+        let escape = encoded
+            .get(index + 1..index + 3)
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match (encoded[index], escape) {
+            (b'%', Some(byte)) => {
+                decoded.push(byte);
+                index += 3;
+            }
+            (byte, _) => {
+                decoded.push(byte);
+                index += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+/// Whether `name`, as scoped by the assembler, may be written `word` in the
+/// source: `.loop` for a local label or `1b` for an anonymous one.
+fn written_as(name: &str, word: &str) -> bool {
+    let source_name = Label::source_name(name);
+    if Label::is_local(word) {
+        name.ends_with(word)
+    } else if Label::is_anonymous(source_name) && name != source_name {
+        word == source_name
+            || word.strip_suffix(['b', 'f']) == Some(source_name)
+    } else {
+        name == word
+    }
+}
+
+/// Words of a line along with their range in characters.
+fn words(text: &str) -> Vec<(usize, usize, String)> {
+    let is_word = |chr: char| is_identifier_char(chr) || chr == '.';
+    let mut words = vec![];
+    let mut current: Option<(usize, String)> = None;
+    for (index, chr) in text.chars().chain(Some(' ')).enumerate() {
+        match (&mut current, is_word(chr)) {
+            (Some((_, word)), true) => word.push(chr),
+            (None, true) => current = Some((index, chr.to_string())),
+            (Some(_), false) => {
+                let (start, word) = current.take().unwrap();
+                words.push((start, index, word));
+            }
+            (None, false) => (),
+        }
+    }
+    words
+}
+
+fn position(line: usize, character: usize) -> Value {
+    json!({ "line": line.saturating_sub(1), "character": character })
+}
+
+fn range(line: usize, start: usize, end: usize) -> Value {
+    json!({ "start": position(line, start), "end": position(line, end) })
+}
+
+/// Names of the symbols referenced by an instruction.
+fn references(statement: &Statement) -> impl Iterator<Item = &String> {
+    statement
+        .instruction
+        .addresses()
+        .into_iter()
+        .filter_map(|address| match address {
+            Address::Symbolic(name) | Address::RelativeSymbolic(name) => Some(name),
+            Address::Raw(_) | Address::Relative(_) => None,
+        })
+}
+
+/// Encoded words of an instruction along with the fields of its first word.
+fn encoding(statement: &Statement, symbols: &HashMap<String, u16>) -> String {
+    let words = statement.instruction.to_binary(statement.address, symbols);
+    let hex: Vec<String> = words.iter().map(|word| format!("{:04X}", word)).collect();
+    let mut text = format!(
+        "`{}` at 0x{:04X}: `{}`\n",
+        statement.instruction,
+        statement.address,
+        hex.join(" ")
+    );
+
+    if let Some(format) = Format::of(words[0]) {
+        let fields = format.fields();
+        let mut end = 16;
+        let values: Vec<String> = fields
+            .iter()
+            .map(|(_, width)| {
+                end -= width;
+                format!("{:0width$b}", words[0].get_bits(end..end + width), width = width)
+            })
+            .collect();
+        let names: Vec<&str> = fields.iter().map(|(name, _)| *name).collect();
+        text.push_str(&format!(
+            "\n| {} |\n|{}\n| {} |\n",
+            names.join(" | "),
+            "---|".repeat(names.len()),
+            values.join(" | ")
+        ));
+    }
+    text
+}
+
+impl Document {
+    fn new(uri: &str, text: &str) -> Document {
+        let path = path(uri);
+        let read = |path: &Path| std::fs::read_to_string(path).ok();
+        let (source, include_error) = match Source::load(&path, text, &[], &read) {
+            Ok(source) => (source, None),
+            Err(error) if error.file == path => (Source::single(&path, text), Some(error.diagnostic)),
+            Err(error) => {
+                let diagnostic = Diagnostic::error(
+                    1,
+                    format!("{}:{}: {}", error.file, error.diagnostic.line, error.diagnostic.message),
+                );
+                (Source::single(&path, text), Some(diagnostic))
+            }
+        };
+        let program = Program::parse(&source.text);
+        Document {
+            uri: uri.to_owned(),
+            source,
+            program,
+            include_error,
+        }
+    }
+
+    /// Diagnostics of the lines of the file itself.
+    fn diagnostics(&self) -> Value {
+        let program = self.program.diagnostics().into_iter().filter_map(|diagnostic| {
+            let (file, line) = self.source.origin(diagnostic.line);
+            let text = self.source.line(diagnostic.line);
+            (file == self.source.files[0]).then_some((text, Diagnostic { line, ..diagnostic }))
+        });
+        let includes = self
+            .include_error
+            .iter()
+            .map(|diagnostic| (self.source.line(diagnostic.line), diagnostic.clone()));
+
+        let diagnostics: Vec<Value> = program
+            .chain(includes)
+            .map(|(text, diagnostic)| {
+                json!({
+                    "range": range(diagnostic.line, 0, text.chars().count()),
+                    "severity": match diagnostic.severity {
+                        Severity::Error => DIAGNOSTIC_ERROR,
+                        Severity::Warning => DIAGNOSTIC_WARNING,
+                    },
+                    "source": "apassembler",
+                    "message": diagnostic.message,
+                })
+            })
+            .collect();
+        json!({ "uri": self.uri, "diagnostics": diagnostics })
+    }
+
+    /// Line of the expanded source and word under a position of the file.
+    fn word(&self, params: &Value) -> Option<(usize, String)> {
+        let line = params["position"]["line"].as_u64()? as usize + 1;
+        let character = params["position"]["character"].as_u64()? as usize;
+        let line = self.source.expanded(line)?;
+        words(self.source.line(line))
+            .into_iter()
+            .find(|(start, end, _)| (*start..=*end).contains(&character))
+            .map(|(_, _, word)| (line, word))
+    }
+
+    /// Scoped name of the symbol under a position of the file.
+    fn symbol(&self, params: &Value) -> Option<String> {
+        let (line, word) = self.word(params)?;
+        let labels = self
+            .program
+            .labels()
+            .filter(|(number, _)| *number == line)
+            .map(|(_, label)| &label.name);
+        let referenced = self
+            .program
+            .statements
+            .iter()
+            .filter(|statement| statement.line == line)
+            .flat_map(references);
+        labels
+            .chain(referenced)
+            .find(|name| written_as(name, &word))
+            .cloned()
+            .or_else(|| self.program.symbols.contains_key(&word).then_some(word))
+    }
+
+    /// Location of a symbol written on a line of the expanded source.
+    fn location(&self, line: usize, name: &str) -> Value {
+        let (file, number) = self.source.origin(line);
+        let uri = if file == self.source.files[0] {
+            self.uri.clone()
+        } else {
+            format!("file://{}", file)
+        };
+        let text = self.source.line(line);
+        let (start, end) = words(text)
+            .into_iter()
+            .find(|(_, _, word)| written_as(name, word))
+            .map_or((0, text.chars().count()), |(start, end, _)| (start, end));
+        json!({ "uri": uri, "range": range(number, start, end) })
+    }
+
+    fn definition(&self, params: &Value) -> Value {
+        let Some(name) = self.symbol(params) else {
+            return Value::Null;
+        };
+        self.program
+            .labels()
+            .find(|(_, label)| label.name == name)
+            .map_or(Value::Null, |(line, _)| self.location(line, &name))
+    }
+
+    fn references(&self, params: &Value) -> Value {
+        let Some(name) = self.symbol(params) else {
+            return json!([]);
+        };
+        let declarations = self
+            .program
+            .labels()
+            .filter(|(_, label)| label.name == name)
+            .map(|(line, _)| line)
+            .filter(|_| params["context"]["includeDeclaration"].as_bool().unwrap_or(false));
+        let mut lines: Vec<usize> = self
+            .program
+            .statements
+            .iter()
+            .filter(|statement| references(statement).any(|reference| *reference == name))
+            .map(|statement| statement.line)
+            .chain(declarations)
+            .collect();
+        lines.sort_unstable();
+        lines.dedup();
+        lines.into_iter().map(|line| self.location(line, &name)).collect()
+    }
+
+    fn hover(&self, params: &Value) -> Value {
+        let contents = match self.symbol(params) {
+            Some(name) if self.program.symbols.contains_key(&name) => {
+                format!("`{}` = 0x{:04X}", name, self.program.symbols[&name])
+            }
+            _ => {
+                let Some((line, _)) = self.word(params) else {
+                    return Value::Null;
+                };
+                // Fields that do not fit cannot be shown encoded.
+                if self.program.validate().iter().any(|(invalid, _)| *invalid == line) {
+                    return Value::Null;
+                }
+                let encodings: Vec<String> = self
+                    .program
+                    .statements
+                    .iter()
+                    .filter(|statement| statement.line == line)
+                    .map(|statement| encoding(statement, &self.program.symbols))
+                    .collect();
+                if encodings.is_empty() {
+                    return Value::Null;
+                }
+                encodings.join("\n")
+            }
+        };
+        json!({ "contents": { "kind": "markdown", "value": contents } })
+    }
+
+    fn symbols(&self) -> Value {
+        self.program
+            .labels()
+            .filter(|(_, label)| !Label::is_internal(&label.name))
+            .filter_map(|(line, label)| {
+                let (file, number) = self.source.origin(line);
+                if file != self.source.files[0] {
+                    return None;
+                }
+                let width = self.source.line(line).chars().count();
+                let address = self.program.symbols.get(&label.name);
+                Some(json!({
+                    "name": label.name,
+                    "detail": address.map(|address| format!("0x{:04X}", address)),
+                    "kind": SYMBOL_FUNCTION,
+                    "range": range(number, 0, width),
+                    "selectionRange": range(number, 0, width),
+                }))
+            })
+            .collect()
+    }
+}
+
+fn operands(operands: &[OperandKind]) -> String {
+    operands
+        .iter()
+        .map(OperandKind::name)
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Mnemonics, pseudo instructions and registers.
+fn completion() -> Value {
+    let mnemonics = OPCODES.values().map(|mnemonic| {
+        json!({
+            "label": mnemonic.name,
+            "kind": COMPLETION_KEYWORD,
+            "detail": format!("{} {}", mnemonic.format.name(), operands(mnemonic.operands)),
+        })
+    });
+    let pseudo = PSEUDO.values().map(|pseudo| {
+        json!({
+            "label": pseudo.name,
+            "kind": COMPLETION_KEYWORD,
+            "detail": format!("{} {}", pseudo.name, operands(pseudo.operands)),
+            "documentation": pseudo.expansion.join("; "),
+        })
+    });
+    let registers = (0..REGISTERS).map(|number| {
+        json!({ "label": format!("R{}", number), "kind": COMPLETION_VARIABLE })
+    });
+    mnemonics.chain(pseudo).chain(registers).collect()
+}
+
+#[derive(Default)]
+struct Server {
+    documents: HashMap<String, Document>,
+    /// Notifications waiting to be sent to the editor.
+    notifications: Vec<Value>,
+}
+
+impl Server {
+    fn publish(&mut self, diagnostics: Value) {
+        self.notifications.push(json!({
+            "jsonrpc": "2.0",
+            "method": "textDocument/publishDiagnostics",
+            "params": diagnostics,
+        }));
+    }
+
+    fn open(&mut self, uri: &str, text: &str) {
+        let document = Document::new(uri, text);
+        self.publish(document.diagnostics());
+        self.documents.insert(uri.to_owned(), document);
+    }
+
+    fn handle(&mut self, method: &str, params: &Value) -> Result<Value, (i64, String)> {
+        let uri = params["textDocument"]["uri"].as_str().unwrap_or_default();
+        let document = self.documents.get(uri);
+        match (method, document) {
+            ("initialize", _) => Ok(json!({
+                "capabilities": {
+                    "textDocumentSync": SYNC_FULL,
+                    "definitionProvider": true,
+                    "referencesProvider": true,
+                    "hoverProvider": true,
+                    "completionProvider": {},
+                    "documentSymbolProvider": true,
+                },
+                "serverInfo": { "name": "apassembler", "version": clap::crate_version!() },
+            })),
+            ("shutdown", _) => Ok(Value::Null),
+            ("textDocument/didOpen", _) => {
+                let text = params["textDocument"]["text"].as_str().unwrap_or_default();
+                self.open(uri, text);
+                Ok(Value::Null)
+            }
+            ("textDocument/didChange", _) => {
+                let changes = params["contentChanges"].as_array();
+                if let Some(text) = changes.and_then(|changes| changes.last()?["text"].as_str()) {
+                    self.open(uri, text);
+                }
+                Ok(Value::Null)
+            }
+            ("textDocument/didClose", _) => {
+                self.documents.remove(uri);
+                self.publish(json!({ "uri": uri, "diagnostics": [] }));
+                Ok(Value::Null)
+            }
+            ("textDocument/definition", Some(document)) => Ok(document.definition(params)),
+            ("textDocument/references", Some(document)) => Ok(document.references(params)),
+            ("textDocument/hover", Some(document)) => Ok(document.hover(params)),
+            ("textDocument/documentSymbol", Some(document)) => Ok(document.symbols()),
+            ("textDocument/completion", _) => Ok(completion()),
+            (
+                "textDocument/definition" | "textDocument/hover" | "textDocument/references"
+                | "textDocument/documentSymbol",
+                None,
+            ) => Ok(Value::Null),
+            _ => Err((METHOD_NOT_FOUND, format!("{} is not supported", method))),
+        }
+    }
+}
+
+fn read_message(input: &mut impl BufRead) -> io::Result<Option<Value>> {
+    let mut length = None;
+    loop {
+        let mut header = String::new();
+        if input.read_line(&mut header)? == 0 {
+            return Ok(None);
+        }
+        let header = header.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            if name.eq_ignore_ascii_case("Content-Length") {
+                length = value.trim().parse::<usize>().ok();
+            }
+        }
+    }
+
+    let length = length.ok_or_else(|| {
+        io::Error::new(io::ErrorKind::InvalidData, "message without a Content-Length")
+    })?;
+    let mut content = vec![0; length];
+    input.read_exact(&mut content)?;
+    serde_json::from_slice(&content).map(Some).map_err(io::Error::from)
+}
+
+fn write_message(output: &mut impl Write, message: &Value) -> io::Result<()> {
+    let content = message.to_string();
+    write!(output, "Content-Length: {}\r\n\r\n{}", content.len(), content)?;
+    output.flush()
+}
+
+/// Answer the requests of an editor until it asks the server to exit.
+pub fn serve(input: &mut impl BufRead, output: &mut impl Write) -> io::Result<()> {
+    let mut server = Server::default();
+
+    while let Some(message) = read_message(input)? {
+        let method = message["method"].as_str().unwrap_or_default();
+        if method == "exit" {
+            break;
+        }
+        let result = server.handle(method, &message["params"]);
+
+        for notification in server.notifications.drain(..) {
+            write_message(output, &notification)?;
+        }
+        // Notifications have no identifier and expect no response.
+        if let Some(id) = message.get("id") {
+            let response = match result {
+                Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+                Err((code, message)) => json!({
+                    "jsonrpc": "2.0",
+                    "id": id,
+                    "error": { "code": code, "message": message },
+                }),
+            };
+            write_message(output, &response)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const URI: &str = "file:///project/main%20loop.asm";
+
+    /// Run a session and return the messages sent by the server.
+    fn session(requests: &[(&str, Value)]) -> Vec<Value> {
+        let mut input = vec![];
+        for (id, (method, params)) in requests.iter().enumerate() {
+            let mut message = json!({ "jsonrpc": "2.0", "method": method, "params": params });
+            if !method.starts_with("textDocument/did") {
+                message["id"] = json!(id);
+            }
+            write_message(&mut input, &message).unwrap();
+        }
+
+        let mut output = vec![];
+        serve(&mut input.as_slice(), &mut output).unwrap();
+        let mut output = output.as_slice();
+        std::iter::from_fn(|| read_message(&mut output).unwrap()).collect()
+    }
+
+    fn open(text: &str) -> (&'static str, Value) {
+        (
+            "textDocument/didOpen",
+            json!({ "textDocument": { "uri": URI, "languageId": "apas", "version": 1, "text": text } }),
+        )
+    }
+
+    fn at(method: &'static str, line: usize, character: usize) -> (&'static str, Value) {
+        (
+            method,
+            json!({
+                "textDocument": { "uri": URI },
+                "position": { "line": line, "character": character },
+                "context": { "includeDeclaration": true },
+            }),
+        )
+    }
+
+    #[test]
+    fn diagnostics() {
+        let messages = session(&[
+            ("initialize", json!({})),
+            open("Loop: DEC R0\nBEQX R1\nJNE Loop\n"),
+            ("shutdown", Value::Null),
+        ]);
+        assert_eq!(messages[0]["result"]["capabilities"]["hoverProvider"], true);
+        assert_eq!(messages[1]["method"], "textDocument/publishDiagnostics");
+        assert_eq!(messages[1]["params"]["uri"], URI);
+        assert_eq!(
+            messages[1]["params"]["diagnostics"],
+            json!([{
+                "range": range(2, 0, 7),
+                "severity": DIAGNOSTIC_ERROR,
+                "source": "apassembler",
+                "message": "unknown instruction BEQX",
+            }])
+        );
+        assert_eq!(messages[2]["result"], Value::Null);
+    }
+
+    #[test]
+    fn navigation() {
+        let source = "Start: LI R0, 3\n.loop: DEC R0\n  JNE .loop\n  B @Start\n";
+        let messages = session(&[
+            open(source),
+            at("textDocument/definition", 2, 8),
+            at("textDocument/references", 0, 2),
+            at("textDocument/documentSymbol", 0, 0),
+        ]);
+        assert_eq!(messages[1]["result"], json!({ "uri": URI, "range": range(2, 0, 5) }));
+        assert_eq!(
+            messages[2]["result"],
+            json!([
+                { "uri": URI, "range": range(1, 0, 5) },
+                { "uri": URI, "range": range(4, 5, 10) },
+            ])
+        );
+        let names: Vec<&Value> = messages[3]["result"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|symbol| &symbol["name"])
+            .collect();
+        assert_eq!(names, vec!["Start", "Start.loop"]);
+    }
+
+    #[test]
+    fn hover_and_completion() {
+        let messages = session(&[
+            open("Start: LI R0, 3\nDEC R0\n"),
+            at("textDocument/hover", 1, 1),
+            at("textDocument/hover", 0, 2),
+            at("textDocument/completion", 1, 0),
+            ("workspace/symbol", json!({})),
+        ]);
+        assert_eq!(
+            messages[1]["result"]["contents"]["value"],
+            "`DEC R0` at 0x0002: `E200`\n\n| format | opcode | unused | tsd mode | tsd |\n|---|---|---|---|---|\n| 11100 | 010 | 00 | 00 | 0000 |\n"
+        );
+        assert_eq!(messages[2]["result"]["contents"]["value"], "`Start` = 0x0000");
+        let labels: Vec<&str> = messages[3]["result"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .filter_map(|item| item["label"].as_str())
+            .collect();
+        assert!(labels.contains(&"LIMI") && labels.contains(&"CALL") && labels.contains(&"R15"));
+        assert_eq!(messages[4]["error"]["code"], METHOD_NOT_FOUND);
+    }
+
+    #[test]
+    fn hover_invalid() {
+        let messages = session(&[
+            open("ADD R20, R0\nSLL 16, R1\nROI\n"),
+            at("textDocument/hover", 0, 0),
+            at("textDocument/hover", 1, 0),
+            at("textDocument/hover", 2, 0),
+        ]);
+        assert_eq!(messages[1]["result"], Value::Null);
+        assert_eq!(messages[2]["result"], Value::Null);
+        assert!(messages[3]["result"]["contents"]["value"].as_str().unwrap().starts_with("`ROI`"));
+    }
+
+    #[test]
+    fn uri_path() {
+        assert_eq!(path(URI), "/project/main loop.asm");
+    }
+}
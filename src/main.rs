@@ -1,83 +1,841 @@
-use clap::{arg, command, ArgAction};
-use parser::instruction::Instruction;
-use std::{
-    collections::HashMap,
-    fs::File,
-    io::{BufRead, BufReader},
-    path::Path,
-};
+use assembler::Program;
+use clap::{arg, command, ArgAction, ArgMatches, Command};
+use cosim::Columns;
+use diagnostic::{Diagnostic, Severity};
+use lint::{Level, Lint, Lints};
+use memory::MemoryMap;
+use object::Object;
+use simulator::Machine;
+use source::Source;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::exit;
+use std::time::{Duration, SystemTime};
+use vcd::Trace;
 
+mod assembler;
+mod cosim;
+mod diagnostic;
+mod disassembler;
+mod format;
+mod graph;
+mod isa;
+mod linker;
+mod lint;
+mod listing;
+mod lsp;
+mod memory;
+mod object;
+mod output;
 mod parser;
+mod placement;
+mod simulator;
+mod source;
+mod symbols;
+mod testing;
+mod validation;
+mod vcd;
 
-fn parse_instruction(filepath: &Path) -> Vec<Instruction> {
-    let file = File::open(filepath).expect("Cannot open file");
+/// The sources have errors.
+const EXIT_ERRORS: i32 = 1;
+/// The command could not run, such as when a file cannot be read.
+const EXIT_FAILURE: i32 = 2;
 
-    let reader: BufReader<File> = BufReader::new(file);
-    let mut result = vec![];
+/// Delay between two checks for changes with `--watch`.
+const WATCH_INTERVAL: Duration = Duration::from_millis(200);
 
-    for line in reader.lines() {
-        let line = line.expect("Cannot read line");
+/// Amount of messages selected with `--quiet` and `--verbose`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Verbosity {
+    /// Errors only.
+    Quiet,
+    Normal,
+    /// Progress and statistics as well.
+    Verbose,
+}
+
+fn verbosity(matches: &ArgMatches) -> Verbosity {
+    if flag(matches, "quiet") {
+        Verbosity::Quiet
+    } else if flag(matches, "verbose") {
+        Verbosity::Verbose
+    } else {
+        Verbosity::Normal
+    }
+}
+
+/// Value of a flag, false when the subcommand does not take it.
+fn flag(matches: &ArgMatches, id: &str) -> bool {
+    matches
+        .try_get_one::<bool>(id)
+        .ok()
+        .flatten()
+        .copied()
+        .unwrap_or(false)
+}
+
+fn fail(message: impl std::fmt::Display) -> ! {
+    eprintln!("apassembler: {}", message);
+    exit(EXIT_FAILURE)
+}
+
+/// Read a whole file at once, `-` standing for the standard input.
+fn try_read(path: &str) -> Result<String, String> {
+    if path == "-" {
+        let mut source = String::new();
+        std::io::stdin()
+            .read_to_string(&mut source)
+            .map_err(|error| format!("cannot read standard input: {}", error))?;
+        Ok(source)
+    } else {
+        std::fs::read_to_string(path).map_err(|error| format!("cannot read {}: {}", path, error))
+    }
+}
+
+fn read(path: &str) -> String {
+    try_read(path).unwrap_or_else(|message| fail(message))
+}
+
+/// Read a file given to a subcommand. With `--watch` a file that cannot be
+/// read, such as one being saved, only fails the run and `None` is returned.
+fn read_input(matches: &ArgMatches, path: &str) -> Option<String> {
+    match try_read(path) {
+        Ok(text) => Some(text),
+        Err(message) if flag(matches, "watch") => {
+            eprintln!("apassembler: {}", message);
+            None
+        }
+        Err(message) => fail(message),
+    }
+}
+
+/// Name of a file in diagnostics.
+fn display(path: &str) -> &str {
+    if path == "-" {
+        "<stdin>"
+    } else {
+        path
+    }
+}
+
+/// Write to the file given with `--output`, or to the standard output.
+fn output(matches: &ArgMatches, content: &[u8]) {
+    write(matches.value_of("output").unwrap_or("-"), content)
+}
+
+/// Write to a file, `-` standing for the standard output.
+fn write(path: &str, content: &[u8]) {
+    let result = match path {
+        "-" => std::io::stdout().write_all(content),
+        path => std::fs::write(path, content),
+    };
+    result.unwrap_or_else(|error| fail(format!("cannot write {}: {}", display(path), error)))
+}
+
+/// Print diagnostics located by `origin`, warnings are hidden by `--quiet`.
+/// Returns whether any of them is an error.
+fn report(
+    diagnostics: &[Diagnostic],
+    origin: impl Fn(usize) -> (String, usize),
+    verbosity: Verbosity,
+) -> bool {
+    for diagnostic in diagnostics {
+        if diagnostic.severity == Severity::Warning && verbosity == Verbosity::Quiet {
+            continue;
+        }
+        let (file, line) = origin(diagnostic.line);
+        eprintln!(
+            "{}:{}: {}: {}",
+            file, line, diagnostic.severity, diagnostic.message
+        );
+    }
+    diagnostics
+        .iter()
+        .any(|diagnostic| diagnostic.severity == Severity::Error)
+}
+
+fn parse_size(value: &str) -> u32 {
+    match value.strip_prefix("0x") {
+        Some(hex) => u32::from_str_radix(hex, 16),
+        None => value.parse(),
+    }
+    .unwrap_or_else(|_| fail(format!("invalid memory size {}", value)))
+}
+
+/// Parse a `NAME=VALUE` definition, the value defaults to 1.
+fn parse_define(definition: &str) -> (&str, u16) {
+    let (name, value) = definition.split_once('=').unwrap_or((definition, "1"));
+    match parser::number::parse_u16(value) {
+        Ok(("", value)) => (name, value),
+        _ => fail(format!("invalid value in definition {}", definition)),
+    }
+}
 
-        if !line.is_empty() {
-            match parser::parse_line(&line) {
-                Ok((_, ins)) => result.push(ins),
-                Err(_why) => (),
+/// Outcome of a subcommand run on a source file.
+struct Run {
+    /// Every file read, watched with `--watch`.
+    files: Vec<String>,
+    success: bool,
+}
+
+impl Run {
+    fn new(source: &Source, success: bool) -> Run {
+        Run {
+            files: source.files.clone(),
+            success,
+        }
+    }
+}
+
+/// Assemble the source file given to a subcommand and print its diagnostics.
+/// Returns the program, its expanded source and whether it has errors, or
+/// the failed run when the sources cannot be read.
+fn assemble(matches: &ArgMatches, verbosity: Verbosity) -> Result<(Program, Source, bool), Run> {
+    let path = matches.value_of("FILE").unwrap();
+    let directories: Vec<PathBuf> = matches
+        .values_of("include")
+        .unwrap_or_default()
+        .map(PathBuf::from)
+        .collect();
+    let read_include = |path: &Path| std::fs::read_to_string(path).ok();
+    let Some(text) = read_input(matches, path) else {
+        return Err(Run {
+            files: vec![path.to_owned()],
+            success: false,
+        });
+    };
+    let source = match Source::load(path, &text, &directories, &read_include) {
+        Ok(source) => source,
+        Err(error) => {
+            let file = display(&error.file).to_owned();
+            report(&[error.diagnostic], |line| (file.clone(), line), verbosity);
+            return Err(Run {
+                files: vec![path.to_owned(), error.file],
+                success: false,
+            });
+        }
+    };
+
+    let mut program = Program::parse(&source.text);
+    program.memory_size = parse_size(matches.value_of("memory-size").unwrap());
+    program.relocatable = flag(matches, "compile");
+    if let Some(path) = matches.value_of("layout") {
+        let Some(layout) = read_input(matches, path) else {
+            return Err(Run::new(&source, false));
+        };
+        match MemoryMap::parse(&layout) {
+            Ok(map) => program.set_memory_map(map),
+            Err(diagnostic) => {
+                report(&[diagnostic], |line| (display(path).to_owned(), line), verbosity);
+                return Err(Run::new(&source, false));
             }
         }
     }
+    for definition in matches.values_of("define").unwrap_or_default() {
+        let (name, value) = parse_define(definition);
+        program.define(name, value);
+    }
+    if flag(matches, "relax") {
+        program.relax();
+    }
+
+    let origin = |line| {
+        let (file, line) = source.origin(line);
+        (display(file).to_owned(), line)
+    };
+    let errors = report(&program.diagnostics(), origin, verbosity);
+    if verbosity == Verbosity::Verbose {
+        let words: u32 = program
+            .statements
+            .iter()
+            .map(|statement| u32::from(statement.instruction.size()))
+            .sum();
+        eprintln!(
+            "{}: {} instructions, {} words, {} symbols, {} files",
+            display(path),
+            program.statements.len(),
+            words,
+            program.symbols.len(),
+            source.files.len()
+        );
+    }
+    Ok((program, source, errors))
+}
+
+/// Parsed instructions, followed by their encoding with `--binary` and `--hex`.
+fn debug(matches: &ArgMatches, program: &Program) -> String {
+    let mut result = String::new();
+    for statement in &program.statements {
+        let instruction = &statement.instruction;
+        let words = instruction.to_binary(statement.address, &program.symbols);
+        result.push_str(&format!("{:?}\n", instruction));
+        if flag(matches, "binary") {
+            result.push_str("Binary: ");
+            for word in &words {
+                result.push_str(&format!("{:016b} ", word));
+            }
+            result.push('\n');
+        }
+        if flag(matches, "hex") {
+            result.push_str("Hex: ");
+            for word in &words {
+                result.push_str(&format!("{:04X} ", word));
+            }
+            result.push('\n');
+        }
+    }
     result
 }
 
-fn retrieve_symbols(filepath: &Path) -> HashMap<String, u16> {
-    let file = File::open(filepath).expect("Cannot open file");
+fn asm(matches: &ArgMatches, verbosity: Verbosity) -> Run {
+    let (program, source, errors) = match assemble(matches, verbosity) {
+        Ok(assembled) => assembled,
+        Err(run) => return run,
+    };
+    if errors {
+        return Run::new(&source, false);
+    }
+
+    if let Some(path) = matches.value_of("listing") {
+        write(path, listing::listing(&program, &source).as_bytes());
+    }
+    if program.relocatable {
+        output(matches, Object::from(&program).to_string().as_bytes());
+        return Run::new(&source, true);
+    }
+    let segments = output::segments(&program);
+    match matches.get_one::<String>("format").unwrap().as_str() {
+        "hex" => output(matches, output::hex(&output::image(&segments)).as_bytes()),
+        "bin" => output(matches, &output::binary(&output::image(&segments))),
+        "memh" => output(matches, output::memh(&segments).as_bytes()),
+        _ => output(matches, debug(matches, &program).as_bytes()),
+    }
+    Run::new(&source, true)
+}
+
+/// Level of each lint, they are all allowed unless `--lint` is given.
+fn lints(matches: &ArgMatches) -> Lints {
+    let mut lints = Lints::new(if flag(matches, "lint") {
+        Level::Warn
+    } else {
+        Level::Allow
+    });
+    for (id, level) in [("warn", Level::Warn), ("allow", Level::Allow), ("deny", Level::Deny)] {
+        for name in matches.get_many::<String>(id).unwrap_or_default() {
+            lints.set(Lint::from_name(name).expect("Lint names are checked by clap"), level);
+        }
+    }
+    lints
+}
 
-    let reader: BufReader<File> = BufReader::new(file);
-    let mut result = HashMap::new();
+fn check(matches: &ArgMatches, verbosity: Verbosity) -> Run {
+    let (program, source, errors) = match assemble(matches, verbosity) {
+        Ok(assembled) => assembled,
+        Err(run) => return run,
+    };
+    let origin = |line| {
+        let (file, line) = source.origin(line);
+        (display(file).to_owned(), line)
+    };
+    let denied = report(&lint::lint(&program, &source.text, &lints(matches)), origin, verbosity);
+    Run::new(&source, !errors && !denied)
+}
 
-    for line in reader.lines() {
-        let line = line.expect("Cannot read line");
+fn symbols(matches: &ArgMatches, verbosity: Verbosity) -> Run {
+    let (program, source, errors) = match assemble(matches, verbosity) {
+        Ok(assembled) => assembled,
+        Err(run) => return run,
+    };
+    if errors {
+        return Run::new(&source, false);
+    }
 
-        if !line.is_empty() {
-            match parser::parse_label(&line) {
-                Ok((_, ins)) => {
-                    result.insert(ins.name, ins.address);
-                }
-                Err(_why) => (),
+    let mut symbols: Vec<(&u16, &String)> = program
+        .symbols
+        .iter()
+        .map(|(name, address)| (address, name))
+        .filter(|(_, name)| verbosity == Verbosity::Verbose || !name.contains('#'))
+        .collect();
+    symbols.sort();
+    let table: String = symbols
+        .iter()
+        .map(|(address, name)| format!("{:04X} {}\n", address, name))
+        .collect();
+    output(matches, table.as_bytes());
+    Run::new(&source, true)
+}
+
+fn graph(matches: &ArgMatches, verbosity: Verbosity) -> Run {
+    let (program, source, errors) = match assemble(matches, verbosity) {
+        Ok(assembled) => assembled,
+        Err(run) => return run,
+    };
+    if errors {
+        return Run::new(&source, false);
+    }
+
+    let graph = if flag(matches, "calls") {
+        graph::call_graph(&program)
+    } else {
+        graph::control_flow(&program)
+    };
+    output(matches, graph.as_bytes());
+    Run::new(&source, true)
+}
+
+/// Modification time of every file, `None` for those that cannot be read.
+fn modified(files: &[String]) -> Vec<Option<SystemTime>> {
+    files
+        .iter()
+        .map(|file| std::fs::metadata(file).and_then(|metadata| metadata.modified()).ok())
+        .collect()
+}
+
+/// Run a subcommand on a source file, then again whenever the file, one it
+/// includes or the layout changes with `--watch`. Outputs are only written
+/// by the successful runs.
+fn watch(matches: &ArgMatches, verbosity: Verbosity, command: fn(&ArgMatches, Verbosity) -> Run) {
+    let watching = flag(matches, "watch");
+    if watching && matches.value_of("FILE") == Some("-") {
+        fail("cannot watch the standard input");
+    }
+
+    let mut run = command(matches, verbosity);
+    if !watching {
+        if !run.success {
+            exit(EXIT_ERRORS);
+        }
+        return;
+    }
+
+    loop {
+        let mut files = run.files;
+        files.extend(matches.value_of("layout").map(str::to_owned));
+        files.sort();
+        files.dedup();
+        if verbosity != Verbosity::Quiet {
+            eprintln!("apassembler: watching {} files", files.len());
+        }
+
+        let before = modified(&files);
+        let changed = loop {
+            std::thread::sleep(WATCH_INTERVAL);
+            let after = modified(&files);
+            if let Some(index) = (0..files.len()).find(|index| after[*index] != before[*index]) {
+                break &files[index];
             }
+        };
+        // Let the editor finish writing.
+        std::thread::sleep(WATCH_INTERVAL);
+        if verbosity != Verbosity::Quiet {
+            eprintln!("apassembler: {} changed", display(changed));
         }
+        run = command(matches, verbosity);
     }
+}
 
-    result
+fn run(matches: &ArgMatches, verbosity: Verbosity) -> Run {
+    let (program, source, errors) = match assemble(matches, verbosity) {
+        Ok(assembled) => assembled,
+        Err(run) => return run,
+    };
+    if errors {
+        return Run::new(&source, false);
+    }
+
+    let limit = matches.value_of("steps").unwrap();
+    let limit = limit
+        .parse()
+        .unwrap_or_else(|_| fail(format!("invalid number of steps {}", limit)));
+    let entry = program.statements.first().map_or(0, |statement| statement.address);
+    let mut machine = Machine::new(&output::segments(&program), entry);
+    let vcd = matches.value_of("vcd");
+    let mut trace = vcd.map(|_| Trace::new(&machine));
+    let result = machine.run(limit, |machine, step| {
+        if let Some(trace) = &mut trace {
+            trace.record(machine, step);
+        }
+    });
+    if let (Some(path), Some(trace)) = (vcd, trace) {
+        write(path, trace.finish().as_bytes());
+    }
+
+    match result {
+        Ok(steps) => {
+            if verbosity == Verbosity::Verbose {
+                eprintln!("{}: halted after {} steps", display(&source.files[0]), steps);
+            }
+            output(matches, machine.to_string().as_bytes());
+            Run::new(&source, true)
+        }
+        Err(fault) => {
+            let line = program
+                .statements
+                .iter()
+                .find(|statement| statement.address == machine.pc)
+                .map_or(0, |statement| statement.line);
+            let origin = |line| {
+                let (file, line) = source.origin(line);
+                (display(file).to_owned(), line)
+            };
+            report(&[Diagnostic::error(line, fault)], origin, verbosity);
+            Run::new(&source, false)
+        }
+    }
 }
 
-fn main() {
-    let matches = command!()
-        .arg(arg!(-b --binary).required(false).action(ArgAction::SetTrue))
-        .arg(arg!(-x --hex).required(false).action(ArgAction::SetTrue))
-        .arg(arg!([FILE]))
-        .get_matches();
+fn cosim(matches: &ArgMatches, verbosity: Verbosity) -> Run {
+    let (program, source, errors) = match assemble(matches, verbosity) {
+        Ok(assembled) => assembled,
+        Err(run) => return run,
+    };
+    let path = matches.value_of("LOG").unwrap();
+    let mut run = Run::new(&source, false);
+    run.files.push(path.to_owned());
+    if errors {
+        return run;
+    }
+
+    let columns =
+        Columns::parse(matches.value_of("columns").unwrap()).unwrap_or_else(|error| fail(error));
+    let Some(log) = read_input(matches, path) else {
+        return run;
+    };
+    let log = match cosim::parse_log(&log, &columns) {
+        Ok(log) => log,
+        Err(diagnostic) => {
+            report(&[diagnostic], |line| (display(path).to_owned(), line), verbosity);
+            return run;
+        }
+    };
+    let entry = program.statements.first().map_or(0, |statement| statement.address);
+    let mut machine = Machine::new(&output::segments(&program), entry);
+    let divergence = match cosim::compare(&mut machine, &log) {
+        Ok(count) => {
+            if verbosity != Verbosity::Quiet {
+                eprintln!("{}: {} instructions match", display(path), count);
+            }
+            run.success = true;
+            return run;
+        }
+        Err(divergence) => divergence,
+    };
 
-    let filepath = Path::new(matches.value_of("FILE").expect("No file specified"));
+    eprintln!("{}:{}: error: {}", display(path), divergence.line, divergence.mismatch);
+    eprintln!("    after {} matching instructions", divergence.index);
+    let instruction = divergence
+        .instruction
+        .as_ref()
+        .map_or("invalid instruction".to_owned(), |instruction| instruction.to_string());
+    eprintln!("    {:04X}  {}", divergence.address, instruction);
+    if let Some(statement) = program
+        .statements
+        .iter()
+        .find(|statement| statement.address == divergence.address)
+    {
+        let (file, line) = source.origin(statement.line);
+        eprintln!("    {}:{}: {}", display(file), line, source.line(statement.line).trim());
+    }
+    for (register, rtl, simulated) in divergence.registers() {
+        eprintln!("    R{:<2}  rtl 0x{:04X}  simulator 0x{:04X}", register, rtl, simulated);
+    }
+    run
+}
 
-    let symbols = retrieve_symbols(filepath);
-    let instructions: Vec<Instruction> = parse_instruction(filepath);
+fn test(matches: &ArgMatches, verbosity: Verbosity) -> Run {
+    let (program, source, errors) = match assemble(matches, verbosity) {
+        Ok(assembled) => assembled,
+        Err(run) => return run,
+    };
+    if errors {
+        return Run::new(&source, false);
+    }
 
-    for instruction in instructions {
-        let words = instruction.to_binary(&symbols);
-        println!("{:?}", instruction);
-        if *matches.get_one::<bool>("binary").unwrap() {
-            print!("Binary: ");
-            for word in &words {
-                print!("{:016b} ", word);
+    let limit = matches.value_of("steps").unwrap();
+    let limit = limit
+        .parse()
+        .unwrap_or_else(|_| fail(format!("invalid number of steps {}", limit)));
+    let origin = |line| {
+        let (file, line) = source.origin(line);
+        (display(file).to_owned(), line)
+    };
+    let segments = output::segments(&program);
+    let mut failed = 0;
+    for test in &program.tests {
+        match testing::run(&program, &segments, test, limit) {
+            Ok(steps) => println!("test {} ... ok ({} steps)", test.name, steps),
+            Err(diagnostic) => {
+                println!("test {} ... FAILED", test.name);
+                report(&[diagnostic], origin, verbosity);
+                failed += 1;
             }
-            println!();
         }
-        if *matches.get_one::<bool>("hex").unwrap() {
-            print!("Hex: ");
-            for word in words {
-                print!("{:04X} ", word);
+    }
+    println!("{} passed, {} failed", program.tests.len() - failed, failed);
+    Run::new(&source, failed == 0)
+}
+
+fn disasm(matches: &ArgMatches) {
+    let source = read(matches.value_of("FILE").unwrap());
+    let words: Vec<u16> = source
+        .split_whitespace()
+        .map(|word| {
+            u16::from_str_radix(word.trim_start_matches("0x"), 16)
+                .unwrap_or_else(|_| fail(format!("invalid word {}", word)))
+        })
+        .collect();
+
+    let mut result = String::new();
+    let mut address = 0;
+    while address < words.len() {
+        match disassembler::decode(&words[address..]) {
+            Some((instruction, length)) => {
+                result.push_str(&format!("{:04X}: {}\n", address, instruction));
+                address += length;
+            }
+            None => {
+                result.push_str(&format!("{:04X}: {:04X}\n", address, words[address]));
+                address += 1;
             }
-            println!();
         }
     }
+    output(matches, result.as_bytes());
+}
+
+fn fmt(matches: &ArgMatches) {
+    let path = matches.value_of("FILE").unwrap();
+    let source = read(path);
+    let formatted = format::format(&source);
+    if flag(matches, "check") {
+        if formatted != source {
+            eprintln!("{}: not formatted", display(path));
+            exit(EXIT_ERRORS);
+        }
+        return;
+    }
+    output(matches, formatted.as_bytes());
+}
+
+fn link(matches: &ArgMatches, verbosity: Verbosity) {
+    let mut modules = vec![];
+    for path in matches.values_of("OBJECTS").unwrap() {
+        match Object::parse(&read(path)) {
+            Ok(object) => modules.push((display(path).to_owned(), object)),
+            Err(diagnostic) => {
+                report(&[diagnostic], |line| (display(path).to_owned(), line), verbosity);
+                exit(EXIT_ERRORS);
+            }
+        }
+    }
+
+    match linker::link(&modules) {
+        Ok(image) => output(matches, output::hex(&image).as_bytes()),
+        Err(errors) => {
+            for (module, error) in errors {
+                eprintln!("{}: error: {}", modules[module].0, error);
+            }
+            exit(EXIT_ERRORS);
+        }
+    }
+}
+
+/// Options shared by the subcommands assembling a source file.
+fn assembly(command: Command<'static>) -> Command<'static> {
+    command
+        .arg(arg!(<FILE> "Source file, - for the standard input"))
+        .arg(
+            arg!(-I --include <DIR> "Directory searched for included files")
+                .required(false)
+                .action(ArgAction::Append),
+        )
+        .arg(
+            arg!(-D --define <DEFINITION> "Constant symbol, as NAME or NAME=VALUE")
+                .required(false)
+                .action(ArgAction::Append),
+        )
+        .arg(arg!(--layout <FILE> "Memory map placing the sections into regions").required(false))
+        .arg(
+            arg!(--"memory-size" <WORDS> "Size of the target memory in words")
+                .required(false)
+                .default_value("0x10000"),
+        )
+        .arg(
+            arg!(--relax "Promote out of range relative jumps to absolute branches")
+                .required(false)
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            arg!(-w --watch "Run again whenever the source, an included file or the layout changes")
+                .required(false)
+                .action(ArgAction::SetTrue),
+        )
+}
+
+fn main() {
+    let matches = command!()
+        .subcommand_required(true)
+        .arg_required_else_help(true)
+        .arg(
+            arg!(-q --quiet "Only print errors")
+                .required(false)
+                .action(ArgAction::SetTrue)
+                .global(true)
+                .conflicts_with("verbose"),
+        )
+        .arg(
+            arg!(-v --verbose "Print statistics about the assembled program")
+                .required(false)
+                .action(ArgAction::SetTrue)
+                .global(true),
+        )
+        .subcommand(
+            assembly(Command::new("asm"))
+                .about("Assemble a source file")
+                .arg(arg!(-o --output <FILE> "Write the output to a file").required(false))
+                .arg(
+                    arg!(-f --format <FORMAT> "Output format, debug prints the parsed instructions")
+                        .required(false)
+                        .value_parser(["debug", "hex", "bin", "memh"])
+                        .default_value("debug"),
+                )
+                .arg(
+                    arg!(-l --listing <FILE> "Write a listing of addresses and words, - for the standard output")
+                        .required(false),
+                )
+                .arg(
+                    arg!(-c --compile "Emit a relocatable object instead of absolute code")
+                        .required(false)
+                        .action(ArgAction::SetTrue),
+                )
+                .arg(
+                    arg!(-b --binary "Show the words in binary with the debug format")
+                        .required(false)
+                        .action(ArgAction::SetTrue),
+                )
+                .arg(
+                    arg!(-x --hex "Show the words in hexadecimal with the debug format")
+                        .required(false)
+                        .action(ArgAction::SetTrue),
+                ),
+        )
+        .subcommand(
+            assembly(Command::new("check"))
+                .about("Report errors and warnings without producing output")
+                .arg(
+                    arg!(--lint "Also warn about suspicious code")
+                        .required(false)
+                        .action(ArgAction::SetTrue),
+                )
+                .arg(
+                    arg!(-W --warn <LINT> "Report a lint as a warning, even without --lint")
+                        .required(false)
+                        .action(ArgAction::Append)
+                        .value_parser(Lint::ALL.map(|lint| lint.name())),
+                )
+                .arg(
+                    arg!(-A --allow <LINT> "Silence a lint")
+                        .required(false)
+                        .action(ArgAction::Append)
+                        .value_parser(Lint::ALL.map(|lint| lint.name())),
+                )
+                .arg(
+                    arg!(--deny <LINT> "Report a lint as an error")
+                        .required(false)
+                        .action(ArgAction::Append)
+                        .value_parser(Lint::ALL.map(|lint| lint.name())),
+                ),
+        )
+        .subcommand(
+            assembly(Command::new("symbols"))
+                .about("Print the address of every symbol")
+                .arg(arg!(-o --output <FILE> "Write the table to a file").required(false)),
+        )
+        .subcommand(
+            assembly(Command::new("graph"))
+                .about("Export the control flow graph as Graphviz DOT")
+                .arg(arg!(-o --output <FILE> "Write the graph to a file").required(false))
+                .arg(
+                    arg!(--calls "Export the graph of subroutine calls instead")
+                        .required(false)
+                        .action(ArgAction::SetTrue),
+                ),
+        )
+        .subcommand(
+            assembly(Command::new("run"))
+                .about("Run the assembled program and print the final registers")
+                .arg(arg!(-o --output <FILE> "Write the registers to a file").required(false))
+                .arg(arg!(--vcd <FILE> "Write a value change dump of every step").required(false))
+                .arg(
+                    arg!(--steps <COUNT> "Stop with an error after this many instructions")
+                        .required(false)
+                        .default_value("100000"),
+                ),
+        )
+        .subcommand(
+            assembly(Command::new("cosim"))
+                .about("Run the assembled program along an RTL log and report the first divergence")
+                .arg(arg!(<LOG> "Log of the RTL, one retired instruction per line"))
+                .arg(
+                    arg!(--columns <SPEC> "Columns of the log among pc, register, value and _")
+                        .required(false)
+                        .default_value("pc,register,value"),
+                ),
+        )
+        .subcommand(
+            assembly(Command::new("test"))
+                .about("Run the .test blocks of a source file in the simulator")
+                .arg(
+                    arg!(--steps <COUNT> "Fail a test still running after this many instructions")
+                        .required(false)
+                        .default_value("100000"),
+                ),
+        )
+        .subcommand(
+            Command::new("disasm")
+                .about("Decode a file of hexadecimal words")
+                .arg(arg!(<FILE> "File of words, - for the standard input"))
+                .arg(arg!(-o --output <FILE> "Write the assembly to a file").required(false)),
+        )
+        .subcommand(
+            Command::new("fmt")
+                .about("Print a source file in canonical form")
+                .arg(arg!(<FILE> "Source file, - for the standard input"))
+                .arg(
+                    arg!(-o --output <FILE> "Write the formatted source to a file")
+                        .required(false),
+                )
+                .arg(
+                    arg!(--check "Fail when the file is not formatted instead of printing it")
+                        .required(false)
+                        .action(ArgAction::SetTrue)
+                        .conflicts_with("output"),
+                ),
+        )
+        .subcommand(
+            Command::new("link")
+                .about("Place and resolve relocatable objects into a memory image")
+                .arg(arg!(-o --output <FILE> "Write the image to a file").required(false))
+                .arg(arg!(<OBJECTS>... "Objects produced with asm --compile")),
+        )
+        .subcommand(Command::new("isa").about("Print the supported instruction set"))
+        .subcommand(Command::new("lsp").about("Serve diagnostics, navigation and completion to an editor on the standard streams"))
+        .get_matches();
+
+    let verbosity = verbosity(&matches);
+    match matches.subcommand() {
+        Some(("asm", matches)) => watch(matches, verbosity, asm),
+        Some(("check", matches)) => watch(matches, verbosity, check),
+        Some(("symbols", matches)) => watch(matches, verbosity, symbols),
+        Some(("graph", matches)) => watch(matches, verbosity, graph),
+        Some(("run", matches)) => watch(matches, verbosity, run),
+        Some(("cosim", matches)) => watch(matches, verbosity, cosim),
+        Some(("test", matches)) => watch(matches, verbosity, test),
+        Some(("disasm", matches)) => disasm(matches),
+        Some(("fmt", matches)) => fmt(matches),
+        Some(("link", matches)) => link(matches, verbosity),
+        Some(("isa", _)) => print!("{}", isa::document()),
+        Some(("lsp", _)) => lsp::serve(&mut std::io::stdin().lock(), &mut std::io::stdout().lock())
+            .unwrap_or_else(|error| fail(format!("language server stopped: {}", error))),
+        _ => unreachable!("A subcommand is required"),
+    }
 }
@@ -0,0 +1,184 @@
+use std::fmt;
+
+use crate::diagnostic::{Diagnostic, Severity};
+use crate::parser::number::parse_unsigned;
+
+/// Range of the target memory with its access rights.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MemoryRegion {
+    pub name: String,
+    pub origin: u16,
+    /// Size in words.
+    pub size: u32,
+    pub readable: bool,
+    pub writable: bool,
+    pub executable: bool,
+}
+
+impl MemoryRegion {
+    /// First address past the region.
+    pub fn end(&self) -> u32 {
+        u32::from(self.origin) + self.size
+    }
+}
+
+/// Memory map of the target, read from a layout file such as
+///
+/// ```text
+/// region ROM 0x0000 0x1000 rx
+/// region RAM 0x1000 0x0F00 rw
+/// section .text ROM
+/// section .data RAM
+/// symbol Afficheur 0x1F00
+/// ```
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct MemoryMap {
+    pub regions: Vec<MemoryRegion>,
+    /// Region each section is placed into.
+    pub sections: Vec<(String, usize)>,
+    /// Addresses defined outside the source, typically device ports.
+    pub symbols: Vec<(String, u16)>,
+}
+
+fn parse_number(text: &str) -> Option<u32> {
+    match parse_unsigned(text) {
+        Ok(("", value)) => Some(value),
+        _ => None,
+    }
+}
+
+impl MemoryMap {
+    pub fn parse(source: &str) -> Result<MemoryMap, Diagnostic> {
+        let mut map = MemoryMap::default();
+
+        for (number, line) in source.lines().enumerate() {
+            let error = |message: &str| Diagnostic::error(number + 1, message);
+            let line = line.split('#').next().unwrap_or_default();
+            let address = |text: &str| {
+                parse_number(text)
+                    .and_then(|value| u16::try_from(value).ok())
+                    .ok_or_else(|| error("invalid address"))
+            };
+
+            match line.split_whitespace().collect::<Vec<_>>()[..] {
+                [] => (),
+                ["region", name, origin, size, attributes] => {
+                    let region = MemoryRegion {
+                        name: name.to_owned(),
+                        origin: address(origin)?,
+                        size: parse_number(size).ok_or_else(|| error("invalid size"))?,
+                        readable: attributes.contains('r'),
+                        writable: attributes.contains('w'),
+                        executable: attributes.contains('x'),
+                    };
+                    if region.end() > crate::placement::MEMORY_SIZE {
+                        return Err(error("region runs past the end of the address space"));
+                    }
+                    if attributes.contains(|chr| !"rwx-".contains(chr)) {
+                        return Err(error("attributes are made of r, w and x"));
+                    }
+                    map.regions.push(region)
+                }
+                ["section", name, region] => {
+                    let region = map
+                        .region(region)
+                        .ok_or_else(|| error("unknown region"))?;
+                    map.sections.push((name.to_owned(), region))
+                }
+                ["symbol", name, value] => map.symbols.push((name.to_owned(), address(value)?)),
+                _ => return Err(error("unknown record")),
+            }
+        }
+
+        Ok(map)
+    }
+
+    fn region(&self, name: &str) -> Option<usize> {
+        self.regions.iter().position(|region| region.name == name)
+    }
+
+    /// Region the named section is placed into.
+    pub fn section_region(&self, section: &str) -> Option<&MemoryRegion> {
+        self.sections
+            .iter()
+            .find(|(name, _)| name == section)
+            .map(|(_, region)| &self.regions[*region])
+    }
+}
+
+/// Section that does not fit the memory map.
+#[derive(Debug, PartialEq)]
+pub enum MemoryError {
+    Unmapped(String),
+    Overflow { section: String, region: String, end: u32 },
+    NotExecutable { section: String, region: String },
+}
+
+impl MemoryError {
+    pub fn severity(&self) -> Severity {
+        match self {
+            MemoryError::Unmapped(_) | MemoryError::Overflow { .. } => Severity::Error,
+            MemoryError::NotExecutable { .. } => Severity::Warning,
+        }
+    }
+}
+
+impl fmt::Display for MemoryError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MemoryError::Unmapped(section) => {
+                write!(f, "section {} is not placed in any memory region", section)
+            }
+            MemoryError::Overflow {
+                section,
+                region,
+                end,
+            } => write!(
+                f,
+                "section {} overflows region {} which ends at 0x{:04X}",
+                section,
+                region,
+                end - 1
+            ),
+            MemoryError::NotExecutable { section, region } => write!(
+                f,
+                "section {} holds instructions but region {} is not executable",
+                section, region
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LAYOUT: &str = "# APAS board\nregion ROM 0x0000 0x1000 rx\nregion RAM $1000 3840 rw\n\nsection .text ROM\nsection .data RAM # variables\nsymbol Afficheur 0x1F00\n";
+
+    #[test]
+    fn parse() {
+        let map = MemoryMap::parse(LAYOUT).unwrap();
+        assert_eq!(map.regions.len(), 2);
+        assert_eq!(map.regions[1].end(), 0x1F00);
+        assert!(map.regions[1].writable && !map.regions[1].executable);
+        assert_eq!(map.section_region(".data").map(|region| region.origin), Some(0x1000));
+        assert_eq!(map.section_region(".bss"), None);
+        assert_eq!(map.symbols, vec![("Afficheur".to_owned(), 0x1F00)]);
+    }
+
+    #[test]
+    fn malformed() {
+        assert_eq!(
+            MemoryMap::parse("region ROM 0 0x1000 rx\nsection .text FLASH\n"),
+            Err(Diagnostic::error(2, "unknown region"))
+        );
+        assert_eq!(
+            MemoryMap::parse("region ROM 0xF000 0x2000 rx\n"),
+            Err(Diagnostic::error(1, "region runs past the end of the address space"))
+        );
+        assert_eq!(
+            MemoryMap::parse("region ROM 0 0x1000 rwz\n"),
+            Err(Diagnostic::error(1, "attributes are made of r, w and x"))
+        );
+    }
+}
@@ -0,0 +1,299 @@
+use std::fmt;
+
+use crate::assembler::Program;
+use crate::diagnostic::Diagnostic;
+use crate::parser::address::Address;
+
+/// How a relocated word refers to its symbol.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RelocationKind {
+    /// The whole word is the address of the symbol.
+    Absolute,
+    /// The low byte is the displacement of a type VI jump to the symbol.
+    Relative,
+}
+
+impl RelocationKind {
+    pub fn name(&self) -> &'static str {
+        match self {
+            RelocationKind::Absolute => "absolute",
+            RelocationKind::Relative => "relative",
+        }
+    }
+}
+
+/// Block of words placed together.
+#[derive(Debug, PartialEq)]
+pub struct Section {
+    pub name: String,
+    /// Fixed address, relocatable sections are placed by the linker.
+    pub origin: Option<u16>,
+    pub words: Vec<u16>,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Symbol {
+    pub name: String,
+    pub section: usize,
+    pub offset: u16,
+    /// Visible to the other modules.
+    pub exported: bool,
+}
+
+/// Word to patch once the address of `symbol` is known.
+#[derive(Debug, PartialEq)]
+pub struct Relocation {
+    pub section: usize,
+    pub offset: u16,
+    pub kind: RelocationKind,
+    pub symbol: String,
+}
+
+/// Relocatable object, the output of assembling a single module.
+#[derive(Debug, Default, PartialEq)]
+pub struct Object {
+    pub sections: Vec<Section>,
+    pub symbols: Vec<Symbol>,
+    /// Symbols expected from the other modules.
+    pub imports: Vec<String>,
+    pub relocations: Vec<Relocation>,
+}
+
+impl From<&Program> for Object {
+    fn from(program: &Program) -> Object {
+        let mut object = Object::default();
+        let start = |section: usize| program.sections[section].start;
+
+        for section in &program.sections {
+            object.sections.push(Section {
+                name: section.name.clone(),
+                origin: section.origin,
+                words: vec![],
+            });
+        }
+
+        for statement in &program.statements {
+            let offset = statement.address.wrapping_sub(start(statement.section));
+            for (word, address) in statement.instruction.placed_addresses() {
+                let (kind, name) = match address {
+                    Address::Symbolic(name) => (RelocationKind::Absolute, name),
+                    Address::RelativeSymbolic(name) => (RelocationKind::Relative, name),
+                    Address::Raw(_) | Address::Relative(_) => continue,
+                };
+                // Constants of the memory map are already encoded.
+                if program.symbols.contains_key(name) && !program.symbol_sections.contains_key(name) {
+                    continue;
+                }
+                object.relocations.push(Relocation {
+                    section: statement.section,
+                    offset: offset + word,
+                    kind,
+                    symbol: name.clone(),
+                });
+                if !program.symbols.contains_key(name) && !object.imports.contains(name) {
+                    object.imports.push(name.clone());
+                }
+            }
+            object.sections[statement.section].words.extend(
+                statement
+                    .instruction
+                    .to_binary(statement.address, &program.symbols),
+            );
+        }
+
+        let mut symbols: Vec<(&String, &usize)> = program.symbol_sections.iter().collect();
+        symbols.sort();
+        for (name, section) in symbols {
+            object.symbols.push(Symbol {
+                name: name.clone(),
+                section: *section,
+                offset: program.symbols[name].wrapping_sub(start(*section)),
+                exported: program.globals.iter().any(|(_, global)| global == name),
+            });
+        }
+        object
+    }
+}
+
+impl fmt::Display for Object {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for section in &self.sections {
+            match section.origin {
+                Some(origin) => writeln!(f, "section {} 0x{:04X}", section.name, origin)?,
+                None => writeln!(f, "section {}", section.name)?,
+            }
+            for row in section.words.chunks(8) {
+                let row: Vec<String> = row.iter().map(|word| format!("{:04X}", word)).collect();
+                writeln!(f, "    {}", row.join(" "))?;
+            }
+        }
+        for symbol in &self.symbols {
+            writeln!(
+                f,
+                "{} {} {} 0x{:04X}",
+                if symbol.exported { "export" } else { "local" },
+                symbol.name,
+                symbol.section,
+                symbol.offset
+            )?;
+        }
+        for name in &self.imports {
+            writeln!(f, "import {}", name)?;
+        }
+        for relocation in &self.relocations {
+            writeln!(
+                f,
+                "reloc {} 0x{:04X} {} {}",
+                relocation.section,
+                relocation.offset,
+                relocation.kind.name(),
+                relocation.symbol
+            )?;
+        }
+        Ok(())
+    }
+}
+
+fn parse_word(text: &str) -> Option<u16> {
+    u16::from_str_radix(text.trim_start_matches("0x"), 16).ok()
+}
+
+impl Object {
+    /// Read back an object written with `Display`.
+    pub fn parse(source: &str) -> Result<Object, Diagnostic> {
+        let mut object = Object::default();
+
+        for (number, line) in source.lines().enumerate() {
+            let error = |message: &str| Diagnostic::error(number + 1, message);
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            if fields.is_empty() {
+                continue;
+            }
+
+            if line.starts_with(char::is_whitespace) {
+                let section = object
+                    .sections
+                    .last_mut()
+                    .ok_or_else(|| error("words outside of a section"))?;
+                for field in fields {
+                    section
+                        .words
+                        .push(parse_word(field).ok_or_else(|| error("invalid word"))?);
+                }
+                continue;
+            }
+
+            let section = |text: &str| -> Result<usize, Diagnostic> {
+                text.parse()
+                    .ok()
+                    .filter(|index| *index < object.sections.len())
+                    .ok_or_else(|| error("unknown section"))
+            };
+            match fields[..] {
+                ["section", name] => object.sections.push(Section {
+                    name: name.to_owned(),
+                    origin: None,
+                    words: vec![],
+                }),
+                ["section", name, origin] => object.sections.push(Section {
+                    name: name.to_owned(),
+                    origin: Some(parse_word(origin).ok_or_else(|| error("invalid origin"))?),
+                    words: vec![],
+                }),
+                [visibility @ ("export" | "local"), name, index, offset] => {
+                    let symbol = Symbol {
+                        name: name.to_owned(),
+                        section: section(index)?,
+                        offset: parse_word(offset).ok_or_else(|| error("invalid offset"))?,
+                        exported: visibility == "export",
+                    };
+                    object.symbols.push(symbol)
+                }
+                ["import", name] => object.imports.push(name.to_owned()),
+                ["reloc", index, offset, kind, name] => {
+                    let section = section(index)?;
+                    let offset = parse_word(offset).ok_or_else(|| error("invalid offset"))?;
+                    if usize::from(offset) >= object.sections[section].words.len() {
+                        return Err(error("relocation outside of its section"));
+                    }
+                    let relocation = Relocation {
+                        section,
+                        offset,
+                        kind: match kind {
+                            "absolute" => RelocationKind::Absolute,
+                            "relative" => RelocationKind::Relative,
+                            _ => return Err(error("unknown relocation kind")),
+                        },
+                        symbol: name.to_owned(),
+                    };
+                    object.relocations.push(relocation)
+                }
+                _ => return Err(error("unknown record")),
+            }
+        }
+
+        Ok(object)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn object(source: &str) -> Object {
+        let mut program = Program::parse(source);
+        program.relocatable = true;
+        Object::from(&program)
+    }
+
+    #[test]
+    fn relocations() {
+        let object = object(".global Table\nStart: MOV @Table, R1\nJNE Start\nCALL print\n(0x100) Table\n");
+        assert_eq!(object.sections.len(), 2);
+        assert_eq!(object.sections[0].origin, None);
+        assert_eq!(object.sections[1].origin, Some(0x100));
+        assert_eq!(object.imports, vec!["print"]);
+        let relocations: Vec<(u16, RelocationKind, &str)> = object
+            .relocations
+            .iter()
+            .map(|relocation| (relocation.offset, relocation.kind, relocation.symbol.as_str()))
+            .collect();
+        assert_eq!(
+            relocations,
+            vec![
+                (1, RelocationKind::Absolute, "Table"),
+                (2, RelocationKind::Relative, "Start"),
+                (4, RelocationKind::Absolute, "4#next"),
+                (7, RelocationKind::Absolute, "print"),
+            ]
+        );
+        let table = object.symbols.iter().find(|symbol| symbol.name == "Table").unwrap();
+        assert_eq!((table.section, table.offset, table.exported), (1, 0, true));
+        let start = object.symbols.iter().find(|symbol| symbol.name == "Start").unwrap();
+        assert!(!start.exported);
+        let next = object.symbols.iter().find(|symbol| symbol.name == "4#next").unwrap();
+        assert_eq!((next.section, next.offset, next.exported), (0, 8, false));
+    }
+
+    #[test]
+    fn round_trip() {
+        let object = object("Start: MOV @Table, R1\nJNE Start\nCALL print\n(0x100) Table\nROI\n");
+        assert_eq!(Object::parse(&object.to_string()), Ok(object));
+    }
+
+    #[test]
+    fn malformed() {
+        assert_eq!(
+            Object::parse("section .text\n    D000\nreloc 1 0x0000 absolute Table\n"),
+            Err(Diagnostic::error(3, "unknown section"))
+        );
+        assert_eq!(
+            Object::parse("section .text\n    D000 0003\nreloc 0 0x0050 absolute Table\n"),
+            Err(Diagnostic::error(3, "relocation outside of its section"))
+        );
+        assert_eq!(
+            Object::parse("    D000\n"),
+            Err(Diagnostic::error(1, "words outside of a section"))
+        );
+    }
+}
@@ -0,0 +1,92 @@
+use crate::assembler::Program;
+
+/// Encoded words of every section, each starting at its base address.
+/// Sections following each other in memory are merged.
+pub fn segments(program: &Program) -> Vec<(u16, Vec<u16>)> {
+    let mut blocks: Vec<(u16, Vec<u16>)> = program
+        .sections
+        .iter()
+        .map(|section| (section.start, vec![]))
+        .collect();
+    for statement in &program.statements {
+        let words = statement
+            .instruction
+            .to_binary(statement.address, &program.symbols);
+        blocks[statement.section].1.extend(words);
+    }
+    blocks.retain(|(_, words)| !words.is_empty());
+    blocks.sort_by_key(|(start, _)| *start);
+
+    let mut segments: Vec<(u16, Vec<u16>)> = vec![];
+    for (start, words) in blocks {
+        match segments.last_mut() {
+            Some((base, previous)) if usize::from(*base) + previous.len() == usize::from(start) => {
+                previous.extend(words)
+            }
+            _ => segments.push((start, words)),
+        }
+    }
+    segments
+}
+
+/// Whole memory image from address 0, the gaps between segments are zeroed.
+pub fn image(segments: &[(u16, Vec<u16>)]) -> Vec<u16> {
+    let mut image = vec![];
+    for (base, words) in segments {
+        let base = usize::from(*base);
+        image.resize(image.len().max(base + words.len()), 0);
+        image[base..base + words.len()].copy_from_slice(words);
+    }
+    image
+}
+
+/// Hexadecimal words, in the format read by `--disassemble`.
+pub fn hex(words: &[u16]) -> String {
+    words
+        .chunks(8)
+        .map(|row| {
+            let row: Vec<String> = row.iter().map(|word| format!("{:04X}", word)).collect();
+            row.join(" ") + "\n"
+        })
+        .collect()
+}
+
+/// Raw words, most significant byte first.
+pub fn binary(words: &[u16]) -> Vec<u8> {
+    words.iter().flat_map(|word| word.to_be_bytes()).collect()
+}
+
+/// Hexadecimal words preceded by their `@address`, as read by the Verilog
+/// `$readmemh` task.
+pub fn memh(segments: &[(u16, Vec<u16>)]) -> String {
+    let mut result = String::new();
+    for (base, words) in segments {
+        result.push_str(&format!("@{:04X}\n", base));
+        result.push_str(&hex(words));
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sections_at_their_base() {
+        let program = Program::parse(
+            ".data\nCounter: CLR R1\n.text\nStart: INC @Counter\n.data\nDEC R1\n.text\nJMP Start\n",
+        );
+        assert_eq!(
+            segments(&program),
+            vec![(0, vec![0xE110, 0x0003, 0xF0FD, 0xE301, 0xE201])]
+        );
+
+        let program = Program::parse("ROI\n.section vectors\n(0x100) Reset\nB 0x0\n");
+        assert_eq!(
+            memh(&segments(&program)),
+            "@0000\nE600\n@0100\nE810 0000\n"
+        );
+        assert_eq!(image(&segments(&program))[0xFF..], [0x0000, 0xE810, 0x0000]);
+        assert_eq!(binary(&[0xE810, 0x0001]), vec![0xE8, 0x10, 0x00, 0x01]);
+    }
+}
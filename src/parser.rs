@@ -1,27 +1,206 @@
+use std::fmt;
+
 use nom::character::complete::space0;
-use nom::error::VerboseError;
+use nom::error::{VerboseError, VerboseErrorKind};
 
-use nom::{error::context, sequence::tuple, IResult};
+use nom::{
+    combinator::eof,
+    error::context,
+    sequence::{preceded, tuple},
+    IResult,
+};
 
+use crate::isa;
+use directive::Directive;
+use identifier::parse_identifier;
 use instruction::{parse_instruction, Instruction};
 use label::Label;
 use operand::Operand;
+use pseudo::PseudoInstruction;
 
-mod address;
-pub mod hexadecimal;
+pub mod address;
+pub mod directive;
+pub mod identifier;
+pub mod immediate;
 pub mod instruction;
 pub mod label;
-mod operand;
+pub mod number;
+pub mod operand;
 mod pointer;
-mod register;
+pub mod pseudo;
+pub mod register;
 
 type Res<T, U> = IResult<T, U, VerboseError<T>>;
 
+/// Start of a comment running to the end of the line.
+pub const COMMENT: char = ';';
+
+/// Split a line into its code and its comment, starting with `;`. A `;`
+/// between double quotes, such as in an included path, is part of the code.
+pub fn split_comment(line: &str) -> (&str, Option<&str>) {
+    let mut quoted = false;
+    for (index, chr) in line.char_indices() {
+        match chr {
+            '"' => quoted = !quoted,
+            COMMENT if !quoted => return (&line[..index], Some(&line[index..])),
+            _ => (),
+        }
+    }
+    (line, None)
+}
+
+/// Instruction making up a whole line, nothing may follow it.
 pub fn parse_line(input: &str) -> Res<&str, Instruction> {
-    context("line", tuple((space0, parse_instruction, space0)))(input)
-        .map(|(next_input, (_, res, _))| (next_input, res))
+    context("line", tuple((space0, parse_instruction, space0, eof)))(input)
+        .map(|(next_input, (_, res, _, _))| (next_input, res))
 }
 
 pub fn parse_label(input: &str) -> Res<&str, Label> {
     context("label parser", label::parse_label)(input)
 }
+
+pub fn parse_pseudo(input: &str) -> Res<&str, PseudoInstruction> {
+    context("pseudo instruction parser", pseudo::parse_pseudo)(input)
+}
+
+pub fn parse_directive(input: &str) -> Res<&str, Directive> {
+    context("directive parser", directive::parse_directive)(input)
+}
+
+/// Line the assembler cannot make sense of.
+#[derive(Debug, PartialEq)]
+pub enum SyntaxError {
+    UnknownInstruction(String),
+    /// Known mnemonic whose operands cannot be parsed.
+    InvalidOperands(String),
+    /// Number that does not fit in 32 bits.
+    NumberTooLarge,
+    /// Line of the expansion of a pseudo instruction that cannot be parsed.
+    InvalidExpansion { pseudo: String, expanded: String },
+    /// `.timing_end` without a region to close.
+    UnmatchedTimingEnd,
+    /// `.timing_begin` never closed, with the region name.
+    UnterminatedTiming(Option<String>),
+    UnmatchedTestEnd,
+    UnterminatedTest(String),
+    /// Line of a test other than `.set`, `.call`, `.expect` and `.test_end`.
+    InTest,
+    /// Directive of a test, with its name, outside of one.
+    OutsideTest(String),
+}
+
+impl fmt::Display for SyntaxError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SyntaxError::UnknownInstruction(name) => write!(f, "unknown instruction {}", name),
+            SyntaxError::InvalidOperands(name) => {
+                let operands = isa::lookup(name)
+                    .map(|mnemonic| mnemonic.operands)
+                    .or_else(|| isa::lookup_pseudo(name).map(|pseudo| pseudo.operands))
+                    .unwrap_or_default();
+                match operands {
+                    [] => write!(f, "invalid operands for {}, it takes none", name),
+                    operands => {
+                        let names: Vec<&str> = operands.iter().map(isa::OperandKind::name).collect();
+                        write!(f, "invalid operands for {}, expected {}", name, names.join(", "))
+                    }
+                }
+            }
+            SyntaxError::NumberTooLarge => write!(f, "number too large"),
+            SyntaxError::InvalidExpansion { pseudo, expanded } => {
+                write!(f, "{} expands to {}, which is not a valid instruction", pseudo, expanded)
+            }
+            SyntaxError::UnmatchedTimingEnd => write!(f, ".timing_end without .timing_begin"),
+            SyntaxError::UnterminatedTiming(Some(name)) => write!(f, "timing region {} is never ended", name),
+            SyntaxError::UnterminatedTiming(None) => write!(f, "timing region is never ended"),
+            SyntaxError::UnmatchedTestEnd => write!(f, ".test_end without .test"),
+            SyntaxError::UnterminatedTest(name) => write!(f, "test {} is never ended", name),
+            SyntaxError::InTest => write!(f, "only .set, .call and .expect may appear in a test"),
+            SyntaxError::OutsideTest(name) => write!(f, "{} outside of a test", name),
+        }
+    }
+}
+
+/// Find why a line holding neither a label, a directive nor an instruction
+/// was rejected. Lines not starting with a word, such as raw data, are not
+/// reported.
+pub fn diagnose(input: &str) -> Option<SyntaxError> {
+    let (_, name) = preceded(space0, parse_identifier)(input).ok()?;
+    if isa::lookup(name).is_none() && isa::lookup_pseudo(name).is_none() {
+        Some(SyntaxError::UnknownInstruction(name.to_owned()))
+    } else if too_large(parse_line(input)) {
+        Some(SyntaxError::NumberTooLarge)
+    } else {
+        Some(SyntaxError::InvalidOperands(name.to_owned()))
+    }
+}
+
+/// Whether parsing failed on a number overflowing 32 bits.
+fn too_large<T>(result: Res<&str, T>) -> bool {
+    match result {
+        Err(nom::Err::Failure(error)) => error
+            .errors
+            .iter()
+            .any(|(_, kind)| *kind == VerboseErrorKind::Context(number::TOO_LARGE)),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn whole_word_mnemonics() {
+        for source in ["B R1", "BEQ R1", "BNE *R2", "BGT @Loop", "LI R0, 0x0001", "LIMI R0, 0x0001"] {
+            let (rest, instruction) = parse_line(source).unwrap();
+            assert_eq!((rest, instruction.to_string()), ("", source.to_owned()));
+        }
+        assert!(parse_line("BEQX R1").is_err());
+        assert!(parse_line("LIMIT R0, 1").is_err());
+    }
+
+    #[test]
+    fn comments() {
+        assert_eq!(split_comment("DEC R0 ; count"), ("DEC R0 ", Some("; count")));
+        assert_eq!(split_comment("; alone"), ("", Some("; alone")));
+        assert_eq!(
+            split_comment(".include \"a;b.asm\";"),
+            (".include \"a;b.asm\"", Some(";"))
+        );
+        assert_eq!(split_comment("ROI"), ("ROI", None));
+    }
+
+    #[test]
+    fn unknown_instruction() {
+        assert_eq!(
+            diagnose("  BEQX R1"),
+            Some(SyntaxError::UnknownInstruction("BEQX".to_owned()))
+        );
+        assert_eq!(diagnose("0x0048"), None);
+    }
+
+    #[test]
+    fn invalid_operands() {
+        assert_eq!(
+            diagnose("MOV R1"),
+            Some(SyntaxError::InvalidOperands("MOV".to_owned()))
+        );
+        assert_eq!(
+            diagnose("SLA R0, 20").unwrap().to_string(),
+            "invalid operands for SLA, expected shift, tsd"
+        );
+        assert_eq!(
+            diagnose("ROI R0").unwrap().to_string(),
+            "invalid operands for ROI, it takes none"
+        );
+        assert!(parse_line("ADD R1, R2 garbage").is_err());
+        assert_eq!(parse_line("ADD R1, R2  ").map(|(rest, _)| rest), Ok(""));
+    }
+
+    #[test]
+    fn number_too_large() {
+        assert!(parse_line("LI R0, 0x1FFFFFFFF").is_err());
+        assert_eq!(diagnose("LI R0, 0x1FFFFFFFF"), Some(SyntaxError::NumberTooLarge));
+    }
+}
@@ -1,22 +1,28 @@
-use std::collections::HashMap;
+use std::{collections::HashMap, fmt};
 
-use super::{hexadecimal::raw_16bits, Res};
+use super::{
+    identifier::{is_identifier_char, parse_identifier, parse_local_identifier},
+    number::parse_u16,
+    Res,
+};
 use nom::{
     branch::alt,
-    bytes::complete::{tag, take_while},
+    bytes::complete::{tag, take_while1},
+    character::complete::{digit1, one_of},
+    combinator::{not, recognize},
     error::context,
-    sequence::tuple,
+    sequence::{terminated, tuple},
 };
 pub use relative::parse_relative;
 
 mod relative;
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum Address {
     Raw(u16),
     Symbolic(String),
     RelativeSymbolic(String),
-    Relative(i8),
+    Relative(i16),
 }
 
 impl Address {
@@ -29,18 +35,51 @@ impl Address {
         }
     }
 
-    pub fn resolve_relative(&self) -> Option<i8> {
+    /// Displacement from the word following `address`.
+    pub fn resolve_relative(&self, address: u16, symbols: &HashMap<String, u16>) -> Option<i16> {
         match self {
             Address::Raw(_) => None,
             Address::Relative(value) => Some(*value),
             Address::Symbolic(_) => None,
-            Address::RelativeSymbolic(_) => todo!("Cannot resolve relative symbols yet"),
+            Address::RelativeSymbolic(value) => symbols
+                .get(value)
+                .map(|target| target.wrapping_sub(address.wrapping_add(1)) as i16),
+        }
+    }
+}
+
+impl fmt::Display for Address {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Address::Raw(value) => write!(f, "0x{:04X}", value),
+            Address::Symbolic(name) => write!(f, "@{}", name),
+            Address::RelativeSymbolic(name) => write!(f, "{}", name),
+            Address::Relative(value) => write!(f, "{}", value),
         }
     }
 }
 
-fn sym_address_char(chr: char) -> bool {
-    chr.is_alphanumeric()
+/// Reference to an anonymous label, `1b` for the previous `1:` and `1f` for
+/// the next one.
+fn parse_anonymous_reference(input: &str) -> Res<&str, &str> {
+    context(
+        "anonymous label reference",
+        terminated(
+            recognize(tuple((digit1, one_of("bf")))),
+            not(take_while1(is_identifier_char)),
+        ),
+    )(input)
+}
+
+pub fn parse_symbol(input: &str) -> Res<&str, &str> {
+    context(
+        "symbol",
+        alt((
+            parse_anonymous_reference,
+            parse_local_identifier,
+            parse_identifier,
+        )),
+    )(input)
 }
 
 pub fn parse_address(input: &str) -> Res<&str, Address> {
@@ -48,13 +87,12 @@ pub fn parse_address(input: &str) -> Res<&str, Address> {
 }
 
 fn parse_symbolic(input: &str) -> Res<&str, Address> {
-    context("@Address", tuple((tag("@"), take_while(sym_address_char))))(input)
+    context("@Address", tuple((tag("@"), parse_symbol)))(input)
         .map(|(next_input, (_a, address))| (next_input, Address::Symbolic(address.to_owned())))
 }
 
 pub fn parse_raw_value(input: &str) -> Res<&str, u16> {
-    context("Raw address", tuple((tag("0x"), raw_16bits)))(input)
-        .map(|(next_input, (_prefix, address))| (next_input, address))
+    context("Raw address", parse_u16)(input)
 }
 
 fn parse_raw(input: &str) -> Res<&str, Address> {
@@ -71,6 +109,19 @@ mod tests {
         assert_eq!(parse_raw("0x1102"), Ok(("", Address::Raw(0x1102))))
     }
 
+    #[test]
+    fn decimal_address() {
+        assert_eq!(parse_raw("4_098"), Ok(("", Address::Raw(0x1002))))
+    }
+
+    #[test]
+    fn dotted_address() {
+        assert_eq!(
+            parse_symbolic("@uart.tx_2,"),
+            Ok((",", Address::Symbolic("uart.tx_2".to_owned())))
+        )
+    }
+
     #[test]
     fn address() {
         assert_eq!(
@@ -1,22 +1,62 @@
 use super::{Address, Res};
+use crate::parser::number::parse_signed;
 
-use nom::{branch::alt, bytes::complete::take_while, error::context};
+use nom::{branch::alt, combinator::map_res, error::context};
 
-mod decimal;
-mod hexadecimal;
+fn parse_anonymous(input: &str) -> Res<&str, Address> {
+    context("Anonymous relative address", super::parse_anonymous_reference)(input)
+        .map(|(next_input, address)| (next_input, Address::RelativeSymbolic(address.to_owned())))
+}
+
+fn parse_numeric(input: &str) -> Res<&str, Address> {
+    context("Numeric relative address", map_res(parse_signed, i16::try_from))(input)
+        .map(|(next_input, address)| (next_input, Address::Relative(address)))
+}
 
 fn parse_relative_symbolic(input: &str) -> Res<&str, Address> {
-    context(
-        "Relative symbolic Address",
-        take_while(super::sym_address_char),
-    )(input)
-    .map(|(next_input, address)| (next_input, Address::RelativeSymbolic(address.to_owned())))
+    context("Relative symbolic Address", super::parse_symbol)(input)
+        .map(|(next_input, address)| (next_input, Address::RelativeSymbolic(address.to_owned())))
 }
 
 pub fn parse_relative(input: &str) -> Res<&str, Address> {
     context(
         "Relative address",
-        alt((hexadecimal::parse, decimal::parse, parse_relative_symbolic)),
+        alt((parse_anonymous, parse_numeric, parse_relative_symbolic)),
     )(input)
-    .map(|(next_input, address)| (next_input, address))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn soft_positive() {
+        assert_eq!(parse_relative("8"), Ok(("", Address::Relative(8))));
+        assert_eq!(parse_relative("0x8"), Ok(("", Address::Relative(8))))
+    }
+
+    #[test]
+    fn hard_positive() {
+        assert_eq!(parse_relative("+8"), Ok(("", Address::Relative(8))));
+        assert_eq!(parse_relative("+0x8"), Ok(("", Address::Relative(8))))
+    }
+
+    #[test]
+    fn negative() {
+        assert_eq!(parse_relative("-8"), Ok(("", Address::Relative(-8))));
+        assert_eq!(parse_relative("-0x8"), Ok(("", Address::Relative(-8))))
+    }
+
+    #[test]
+    fn binary() {
+        assert_eq!(parse_relative("-0b11"), Ok(("", Address::Relative(-3))))
+    }
+
+    #[test]
+    fn anonymous() {
+        assert_eq!(
+            parse_relative("0b"),
+            Ok(("", Address::RelativeSymbolic("0b".to_owned())))
+        )
+    }
 }
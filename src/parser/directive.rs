@@ -0,0 +1,221 @@
+use nom::{
+    branch::alt,
+    bytes::complete::{is_not, tag},
+    character::complete::char,
+    character::complete::{satisfy, space0, space1},
+    combinator::{map, not, opt, peek},
+    error::context,
+    sequence::{delimited, preceded, separated_pair, terminated},
+};
+
+use std::fmt;
+
+use super::identifier::{is_identifier_char, parse_identifier, parse_local_identifier};
+use super::immediate::{parse_immediate, Immediate};
+use super::operand::{parse_operand, Operand};
+use super::Res;
+
+/// Assembler directive, a line starting with a dot.
+#[derive(Debug, PartialEq)]
+pub enum Directive {
+    /// Place the following lines into the named section.
+    Section(String),
+    /// Replace the line with the content of a file.
+    Include(String),
+    /// Make a label visible to the other modules of a linked program.
+    Global(String),
+    /// Start a region whose cycles are totalled in the listing.
+    TimingBegin(Option<String>),
+    /// End the innermost timing region.
+    TimingEnd,
+    /// Start a unit test, run by the `test` subcommand.
+    Test(String),
+    TestEnd,
+    /// Give a register or a memory word a value before a call of a test.
+    Set(Operand, Immediate),
+    /// Call a routine from a test.
+    Call(String),
+    /// Check the value of a register or a memory word in a test.
+    Expect(Operand, Immediate),
+}
+
+impl fmt::Display for Directive {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Directive::Section(name) if name == ".text" || name == ".data" => write!(f, "{}", name),
+            Directive::Section(name) => write!(f, ".section {}", name),
+            Directive::Include(path) => write!(f, ".include \"{}\"", path),
+            Directive::Global(name) => write!(f, ".global {}", name),
+            Directive::TimingBegin(Some(name)) => write!(f, ".timing_begin {}", name),
+            Directive::TimingBegin(None) => write!(f, ".timing_begin"),
+            Directive::TimingEnd => write!(f, ".timing_end"),
+            Directive::Test(name) => write!(f, ".test {}", name),
+            Directive::TestEnd => write!(f, ".test_end"),
+            Directive::Set(location, value) => write!(f, ".set {}, {}", location, value),
+            Directive::Call(name) => write!(f, ".call {}", name),
+            Directive::Expect(location, value) => write!(f, ".expect {}, {}", location, value),
+        }
+    }
+}
+
+fn parse_keyword<'a>(keyword: &'static str) -> impl FnMut(&'a str) -> Res<&'a str, &'a str> {
+    terminated(tag(keyword), not(peek(satisfy(is_identifier_char))))
+}
+
+fn parse_section(input: &str) -> Res<&str, Directive> {
+    context(
+        "section directive",
+        alt((
+            map(parse_keyword(".text"), |_| Directive::Section(".text".to_owned())),
+            map(parse_keyword(".data"), |_| Directive::Section(".data".to_owned())),
+            map(
+                preceded(
+                    terminated(parse_keyword(".section"), space1),
+                    alt((parse_local_identifier, parse_identifier)),
+                ),
+                |name: &str| Directive::Section(name.to_owned()),
+            ),
+        )),
+    )(input)
+}
+
+fn parse_include(input: &str) -> Res<&str, Directive> {
+    context(
+        "include directive",
+        map(
+            preceded(
+                terminated(parse_keyword(".include"), space1),
+                delimited(char('"'), is_not("\""), char('"')),
+            ),
+            |path: &str| Directive::Include(path.to_owned()),
+        ),
+    )(input)
+}
+
+fn parse_global(input: &str) -> Res<&str, Directive> {
+    context(
+        "global directive",
+        map(
+            preceded(terminated(parse_keyword(".global"), space1), parse_identifier),
+            |name: &str| Directive::Global(name.to_owned()),
+        ),
+    )(input)
+}
+
+fn parse_timing(input: &str) -> Res<&str, Directive> {
+    context(
+        "timing directive",
+        alt((
+            map(
+                preceded(
+                    parse_keyword(".timing_begin"),
+                    opt(preceded(space1, parse_identifier)),
+                ),
+                |name: Option<&str>| Directive::TimingBegin(name.map(str::to_owned)),
+            ),
+            map(parse_keyword(".timing_end"), |_| Directive::TimingEnd),
+        )),
+    )(input)
+}
+
+fn parse_test(input: &str) -> Res<&str, Directive> {
+    let name = || alt((parse_local_identifier, parse_identifier));
+    let assignment = || {
+        separated_pair(
+            parse_operand,
+            delimited(space0, char(','), space0),
+            parse_immediate,
+        )
+    };
+    context(
+        "test directive",
+        alt((
+            map(
+                preceded(terminated(parse_keyword(".test"), space1), name()),
+                |name: &str| Directive::Test(name.to_owned()),
+            ),
+            map(parse_keyword(".test_end"), |_| Directive::TestEnd),
+            map(
+                preceded(terminated(parse_keyword(".set"), space1), assignment()),
+                |(location, value)| Directive::Set(location, value),
+            ),
+            map(
+                preceded(terminated(parse_keyword(".call"), space1), name()),
+                |name: &str| Directive::Call(name.to_owned()),
+            ),
+            map(
+                preceded(terminated(parse_keyword(".expect"), space1), assignment()),
+                |(location, value)| Directive::Expect(location, value),
+            ),
+        )),
+    )(input)
+}
+
+pub fn parse_directive(input: &str) -> Res<&str, Directive> {
+    context(
+        "directive",
+        delimited(space0, alt((parse_section, parse_include, parse_global, parse_timing, parse_test)), space0),
+    )(input)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sections() {
+        let section = |name: &str| Ok(("", Directive::Section(name.to_owned())));
+        assert_eq!(parse_directive(".text"), section(".text"));
+        assert_eq!(parse_directive("  .data "), section(".data"));
+        assert_eq!(parse_directive(".section .rodata"), section(".rodata"));
+        assert_eq!(parse_directive(".section vectors"), section("vectors"));
+    }
+
+    #[test]
+    fn include() {
+        assert_eq!(
+            parse_directive(".include \"lib/uart.asm\""),
+            Ok(("", Directive::Include("lib/uart.asm".to_owned())))
+        );
+        assert!(parse_directive(".include uart.asm").is_err());
+    }
+
+    #[test]
+    fn global() {
+        assert_eq!(
+            parse_directive(".global print"),
+            Ok(("", Directive::Global("print".to_owned())))
+        );
+        assert!(parse_directive(".global .loop").is_err());
+        assert!(parse_directive(".globals print").is_err());
+    }
+
+    #[test]
+    fn timing() {
+        assert_eq!(
+            parse_directive(".timing_begin isr"),
+            Ok(("", Directive::TimingBegin(Some("isr".to_owned()))))
+        );
+        assert_eq!(parse_directive(".timing_begin"), Ok(("", Directive::TimingBegin(None))));
+        assert_eq!(parse_directive(".timing_end "), Ok(("", Directive::TimingEnd)));
+        assert_eq!(Directive::TimingBegin(Some("isr".to_owned())).to_string(), ".timing_begin isr");
+    }
+
+    #[test]
+    fn test_blocks() {
+        assert_eq!(parse_directive(".test double"), Ok(("", Directive::Test("double".to_owned()))));
+        assert_eq!(parse_directive(".test_end"), Ok(("", Directive::TestEnd)));
+        assert_eq!(parse_directive(".call Double"), Ok(("", Directive::Call("Double".to_owned()))));
+        for source in [".set R0, 0x0002", ".expect @Table, 0x0010", ".set 0x0040, @Table"] {
+            let (rest, directive) = parse_directive(source).unwrap();
+            assert_eq!((rest, directive.to_string()), ("", source.to_owned()));
+        }
+        assert!(parse_directive(".set R0").is_err());
+    }
+
+    #[test]
+    fn not_a_directive() {
+        assert!(parse_directive(".textual").is_err());
+        assert!(parse_directive(".section").is_err());
+    }
+}
@@ -0,0 +1,81 @@
+use super::Res;
+use crate::isa;
+use nom::{
+    bytes::complete::take_while1,
+    character::complete::char,
+    combinator::{recognize, verify},
+    error::context,
+    multi::many0,
+    sequence::{pair, preceded},
+};
+
+pub fn is_identifier_char(chr: char) -> bool {
+    chr.is_alphanumeric() || chr == '_'
+}
+
+/// Single identifier segment, it may not start with a digit.
+fn parse_segment(input: &str) -> Res<&str, &str> {
+    context(
+        "identifier segment",
+        verify(take_while1(is_identifier_char), |segment: &str| {
+            !segment.starts_with(|chr: char| chr.is_ascii_digit())
+        }),
+    )(input)
+}
+
+/// Identifier made of segments separated by dots, such as `uart.tx`.
+pub fn parse_identifier(input: &str) -> Res<&str, &str> {
+    context(
+        "identifier",
+        recognize(pair(parse_segment, many0(preceded(char('.'), parse_segment)))),
+    )(input)
+}
+
+/// Identifier scoped to the preceding global label, such as `.loop`.
+pub fn parse_local_identifier(input: &str) -> Res<&str, &str> {
+    context("local identifier", recognize(preceded(char('.'), parse_identifier)))(input)
+}
+
+/// Register names, mnemonics and pseudo instructions cannot be used as symbols.
+pub fn is_reserved(name: &str) -> bool {
+    let register = name
+        .strip_prefix('R')
+        .map(|number| !number.is_empty() && number.chars().all(|chr| chr.is_ascii_digit()))
+        .unwrap_or(false);
+    register || isa::lookup(name).is_some() || isa::lookup_pseudo(name).is_some()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn underscore() {
+        assert_eq!(parse_identifier("print_char,"), Ok((",", "print_char")))
+    }
+
+    #[test]
+    fn digits() {
+        assert_eq!(parse_identifier("LOOP_2 "), Ok((" ", "LOOP_2")));
+        assert!(parse_identifier("2LOOP").is_err())
+    }
+
+    #[test]
+    fn dotted() {
+        assert_eq!(parse_identifier("uart.tx:"), Ok((":", "uart.tx")));
+        assert_eq!(parse_identifier("uart."), Ok((".", "uart")))
+    }
+
+    #[test]
+    fn local() {
+        assert_eq!(parse_local_identifier(".loop_1"), Ok(("", ".loop_1")))
+    }
+
+    #[test]
+    fn reserved() {
+        assert!(is_reserved("R12"));
+        assert!(is_reserved("MOV"));
+        assert!(!is_reserved("R"));
+        assert!(!is_reserved("Return"));
+    }
+}
@@ -0,0 +1,66 @@
+use std::{collections::HashMap, fmt};
+
+use super::{
+    address::{parse_address, Address},
+    number::parse_signed,
+    Res,
+};
+use nom::{branch::alt, error::context};
+
+/// Value stored in the extension word of a type III instruction.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Immediate {
+    Value(i32),
+    Address(Address),
+}
+
+impl Immediate {
+    pub fn resolve(&self, symbols: &HashMap<String, u16>) -> Option<u16> {
+        match self {
+            Immediate::Value(value) => Some(*value as u16),
+            Immediate::Address(address) => address.resolve(symbols),
+        }
+    }
+}
+
+impl fmt::Display for Immediate {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Immediate::Value(value) if *value < 0 => write!(f, "-0x{:04X}", value.unsigned_abs()),
+            Immediate::Value(value) => write!(f, "0x{:04X}", value),
+            Immediate::Address(address) => write!(f, "{}", address),
+        }
+    }
+}
+
+fn parse_value(input: &str) -> Res<&str, Immediate> {
+    context("immediate value", parse_signed)(input)
+        .map(|(next_input, value)| (next_input, Immediate::Value(value)))
+}
+
+fn parse_symbolic(input: &str) -> Res<&str, Immediate> {
+    context("immediate address", parse_address)(input)
+        .map(|(next_input, address)| (next_input, Immediate::Address(address)))
+}
+
+pub fn parse_immediate(input: &str) -> Res<&str, Immediate> {
+    context("immediate", alt((parse_value, parse_symbolic)))(input)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn value() {
+        assert_eq!(parse_immediate("-0x8"), Ok(("", Immediate::Value(-8))))
+    }
+
+    #[test]
+    fn symbolic() {
+        assert_eq!(
+            parse_immediate("@Table"),
+            Ok(("", Immediate::Address(Address::Symbolic("Table".to_owned()))))
+        )
+    }
+}
@@ -1,13 +1,20 @@
-use std::collections::HashMap;
+use std::{collections::HashMap, fmt};
 
 use bit_field::BitField;
-use phf::phf_map;
 
-use nom::{branch::alt, error::context};
+use nom::{
+    branch::alt,
+    bytes::complete::take_while1,
+    combinator::{map, map_opt, verify},
+    error::context,
+};
 
 use super::address::Address;
+use super::identifier::is_identifier_char;
+use super::immediate::Immediate;
 use super::operand::Operand;
 use super::Res;
+use crate::isa::{self, Format, Mnemonic};
 
 mod i;
 mod ii;
@@ -16,62 +23,6 @@ mod iv;
 mod v;
 mod vi;
 
-static OPCODES: phf::Map<&'static str, u16> = phf_map! {
-    // I
-    "OR" => 0b000,
-    "AND" => 0b001,
-    "XOR" => 0b010,
-    "CMP" => 0b011,
-    "ADD" => 0b110,
-    "STR" => 0b101,
-    "MUL" => 0b110,
-    "MOV" => 0b111,
-    // II
-    "SLL" => 0b000,
-    "SRL" => 0b001,
-    "SLA" => 0b010,
-    "SRA" => 0b011,
-    "ROT" => 0b100,
-    // III
-    "ORI" => 0b0000,
-    "ANDI" => 0b0001,
-    "XORI" => 0b0010,
-    "CI" => 0b0011,
-    "ADDI" => 0b0100,
-    "STRI" => 0b0101,
-    "MULI" => 0b0110,
-    "LI" => 0b1000,
-    "LIMI" => 0b1001,
-    // IV
-    "NOT" => 0b000,
-    "INC" => 0b001,
-    "DEC" => 0b010,
-    "CLR" => 0b011,
-    "PUSH" => 0b100,
-    "PULL" => 0b101,
-    "ROI" => 0b110,
-    "TEST" => 0b111,
-    "SET" => 0b111,
-    // V
-    "B" => 0b000,
-    "BEQ" => 0b001,
-    "BNE" => 0b010,
-    "BC" => 0b011,
-    "BNC" => 0b100,
-    "BGT" => 0b101,
-    "BLT" => 0b110,
-    "BN" => 0b111,
-    // VI
-    "JMP" => 0b000,
-    "JEQ" => 0b001,
-    "JNE" => 0b010,
-    "JC" => 0b011,
-    "JNC" => 0b100,
-    "JGT" => 0b101,
-    "JLT" => 0b110,
-    "JN" => 0b111,
-};
-
 trait Padd<T> {
     fn padd(self) -> T;
 }
@@ -83,7 +34,7 @@ impl Padd<u16> for i8 {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum Instruction {
     I {
         opname: String,
@@ -92,12 +43,12 @@ pub enum Instruction {
     },
     II {
         opname: String,
-        shift: u8,
+        shift: u32,
         tsd: Operand,
     },
     Iii {
         opname: String,
-        immediate: u16,
+        immediate: Immediate,
         tsd: Operand,
     },
     IV {
@@ -114,57 +65,236 @@ pub enum Instruction {
     },
 }
 
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Instruction::I { opname, ts, tsd } => write!(f, "{} {}, {}", opname, ts, tsd),
+            Instruction::II { opname, shift, tsd } => write!(f, "{} {}, {}", opname, shift, tsd),
+            Instruction::Iii {
+                opname,
+                immediate,
+                tsd,
+            } => write!(f, "{} {}, {}", opname, tsd, immediate),
+            Instruction::IV { opname, tsd } => match isa::lookup(opname) {
+                Some(mnemonic) if mnemonic.operands.is_empty() => write!(f, "{}", opname),
+                _ => write!(f, "{} {}", opname, tsd),
+            },
+            Instruction::V { opname, tsd } => write!(f, "{} {}", opname, tsd),
+            Instruction::VI {
+                opname,
+                displacement,
+            } => write!(f, "{} {}", opname, displacement),
+        }
+    }
+}
+
+/// Low 4 bits of a register number or shift count. Validation reports the
+/// values that do not fit, encoding one anyway must not panic.
+fn field(value: u32) -> u16 {
+    (value & 0b1111) as u16
+}
+
+/// Build the first word of an instruction with its format prefix and opcode.
+fn first_word(opname: &str) -> u16 {
+    let mnemonic = isa::lookup(opname).expect("Unknown mnemonic");
+    let (bits, prefix) = mnemonic.format.prefix();
+    *0u16
+        .set_bits(bits, prefix)
+        .set_bits(mnemonic.format.opcode_field(), mnemonic.opcode)
+}
+
+/// Read a whole mnemonic and look it up in the instruction set.
+pub fn parse_mnemonic(input: &str) -> Res<&str, &'static Mnemonic> {
+    context(
+        "mnemonic",
+        map_opt(take_while1(is_identifier_char), isa::lookup),
+    )(input)
+}
+
+/// Recognize a mnemonic of the given format taking `arity` operands.
+///
+/// The whole word is read so `B` does not match the start of `BEQ`.
+fn opname<'a>(format: Format, arity: usize) -> impl FnMut(&'a str) -> Res<&'a str, &'a str> {
+    map(
+        verify(parse_mnemonic, move |mnemonic: &Mnemonic| {
+            mnemonic.format == format && mnemonic.operands.len() == arity
+        }),
+        |mnemonic| mnemonic.name,
+    )
+}
+
 impl Instruction {
-    pub fn to_binary(&self, symtable: &HashMap<String, u16>) -> Vec<u16> {
+    pub fn opname(&self) -> &str {
+        match self {
+            Instruction::I { opname, .. }
+            | Instruction::II { opname, .. }
+            | Instruction::Iii { opname, .. }
+            | Instruction::IV { opname, .. }
+            | Instruction::V { opname, .. }
+            | Instruction::VI { opname, .. } => opname,
+        }
+    }
+
+    pub fn mnemonic(&self) -> &'static Mnemonic {
+        isa::lookup(self.opname()).expect("Unknown mnemonic")
+    }
+
+    /// Every address referenced by the instruction.
+    pub fn addresses(&self) -> Vec<&Address> {
+        fn address(operand: &Operand) -> Option<&Address> {
+            match operand {
+                Operand::NextWord(address) => Some(address),
+                _ => None,
+            }
+        }
+
+        match self {
+            Instruction::I { ts, tsd, .. } => address(ts).into_iter().chain(address(tsd)).collect(),
+            Instruction::Iii {
+                immediate: Immediate::Address(immediate),
+                tsd,
+                ..
+            } => address(tsd).into_iter().chain(Some(immediate)).collect(),
+            Instruction::II { tsd, .. }
+            | Instruction::Iii { tsd, .. }
+            | Instruction::IV { tsd, .. }
+            | Instruction::V { tsd, .. } => address(tsd).into_iter().collect(),
+            Instruction::VI { displacement, .. } => vec![displacement],
+        }
+    }
+
+    pub fn addresses_mut(&mut self) -> Vec<&mut Address> {
+        fn address(operand: &mut Operand) -> Option<&mut Address> {
+            match operand {
+                Operand::NextWord(address) => Some(address),
+                _ => None,
+            }
+        }
+
+        match self {
+            Instruction::I { ts, tsd, .. } => address(ts).into_iter().chain(address(tsd)).collect(),
+            Instruction::Iii {
+                immediate: Immediate::Address(immediate),
+                tsd,
+                ..
+            } => address(tsd).into_iter().chain(Some(immediate)).collect(),
+            Instruction::II { tsd, .. }
+            | Instruction::Iii { tsd, .. }
+            | Instruction::IV { tsd, .. }
+            | Instruction::V { tsd, .. } => address(tsd).into_iter().collect(),
+            Instruction::VI { displacement, .. } => vec![displacement],
+        }
+    }
+
+    /// Every address referenced by the instruction along with the offset of
+    /// the word it is encoded in.
+    pub fn placed_addresses(&self) -> Vec<(u16, &Address)> {
+        fn address(operand: &Operand) -> Option<&Address> {
+            match operand {
+                Operand::NextWord(address) => Some(address),
+                _ => None,
+            }
+        }
+
+        match self {
+            Instruction::I { ts, tsd, .. } => match (address(ts), address(tsd)) {
+                (Some(ts), Some(tsd)) => vec![(1, ts), (2, tsd)],
+                (ts, tsd) => ts.or(tsd).map(|address| (1, address)).into_iter().collect(),
+            },
+            Instruction::Iii { immediate, tsd, .. } => {
+                let immediate = match immediate {
+                    Immediate::Address(address) => Some((1, address)),
+                    Immediate::Value(_) => None,
+                };
+                immediate
+                    .into_iter()
+                    .chain(address(tsd).map(|address| (2, address)))
+                    .collect()
+            }
+            Instruction::II { tsd, .. } | Instruction::IV { tsd, .. } | Instruction::V { tsd, .. } => {
+                address(tsd).map(|address| (1, address)).into_iter().collect()
+            }
+            Instruction::VI { displacement, .. } => vec![(0, displacement)],
+        }
+    }
+
+    /// Cycles taken by the instruction, its addressing modes included.
+    pub fn cycles(&self) -> u32 {
+        fn mode(operand: &Operand) -> u32 {
+            isa::MODES[usize::from(u16::from(operand))].cycles
+        }
+
+        self.mnemonic().cycles
+            + match self {
+                Instruction::I { ts, tsd, .. } => mode(ts) + mode(tsd),
+                Instruction::II { tsd, .. }
+                | Instruction::Iii { tsd, .. }
+                | Instruction::IV { tsd, .. }
+                | Instruction::V { tsd, .. } => mode(tsd),
+                Instruction::VI { .. } => 0,
+            }
+    }
+
+    /// Number of words taken by the encoded instruction.
+    pub fn size(&self) -> u16 {
+        fn extension(operand: &Operand) -> u16 {
+            matches!(operand, Operand::NextWord(_)).into()
+        }
+
+        1 + match self {
+            Instruction::I { ts, tsd, .. } => extension(ts) + extension(tsd),
+            Instruction::Iii { tsd, .. } => 1 + extension(tsd),
+            Instruction::II { tsd, .. } | Instruction::IV { tsd, .. } | Instruction::V { tsd, .. } => {
+                extension(tsd)
+            }
+            Instruction::VI { .. } => 0,
+        }
+    }
+
+    pub fn to_binary(&self, address: u16, symtable: &HashMap<String, u16>) -> Vec<u16> {
         let mut result: Vec<u16> = vec![];
 
         fn get_word(operand: &Operand, symtable: &HashMap<String, u16>) -> Option<u16> {
             match operand {
-                Operand::NextWord(address) => address.resolve(symtable),
+                Operand::NextWord(address) => Some(address.resolve(symtable).unwrap_or(0)),
                 _ => None,
             }
         }
 
         result.push(match self {
-            Instruction::I { opname, ts, tsd } => *0u16
-                .set_bit(15, false)
-                .set_bits(12..=14, *OPCODES.get(opname).unwrap())
+            Instruction::I { opname, ts, tsd } => *first_word(opname)
                 .set_bits(10..=11, ts.into())
-                .set_bits(6..=9, ts.get_register_value())
+                .set_bits(6..=9, field(ts.get_register_value()))
                 .set_bits(4..=5, tsd.into())
-                .set_bits(..=3, tsd.get_register_value()),
-            Instruction::II { opname, shift, tsd } => *0u16
-                .set_bits(14..=15, 0b10)
-                .set_bits(11..=13, *OPCODES.get(opname).unwrap())
+                .set_bits(..=3, field(tsd.get_register_value())),
+            Instruction::II { opname, shift, tsd } => *first_word(opname)
                 .set_bit(10, false)
-                .set_bits(6..=9, (*shift).into())
+                .set_bits(6..=9, field(*shift))
                 .set_bits(4..=5, tsd.into())
-                .set_bits(0..=3, tsd.get_register_value()),
-            Instruction::Iii { opname, immediate: _, tsd } => *0u16
-                .set_bits(13..=15, 0b110)
-                .set_bits(9..=12, *OPCODES.get(opname).unwrap())
+                .set_bits(0..=3, field(tsd.get_register_value())),
+            Instruction::Iii {
+                opname,
+                immediate: _,
+                tsd,
+            } => *first_word(opname)
                 .set_bits(6..=8, 0b000)
                 .set_bits(4..=5, tsd.into())
-                .set_bits(..=3, tsd.get_register_value()),
-            Instruction::IV { opname, tsd } => *0u16
-                .set_bits(11..=15, 0b11100)
-                .set_bits(8..=10, *OPCODES.get(opname).unwrap())
-                .set_bits(6..=7, 0b00)
-                .set_bits(4..=5, tsd.into())
-                .set_bits(..=3, tsd.get_register_value()),
-            Instruction::V { opname, tsd } => *0u16
-                .set_bits(11..=15, 0b11101)
-                .set_bits(8..=10, *OPCODES.get(opname).unwrap())
+                .set_bits(..=3, field(tsd.get_register_value())),
+            Instruction::IV { opname, tsd } | Instruction::V { opname, tsd } => *first_word(opname)
                 .set_bits(6..=7, 0b00)
                 .set_bits(4..=5, tsd.into())
-                .set_bits(..=3, tsd.get_register_value()),
+                .set_bits(..=3, field(tsd.get_register_value())),
             Instruction::VI {
                 opname,
                 displacement,
-            } => *0u16
-                .set_bits(11..=15, 0b11110)
-                .set_bits(8..=10, *OPCODES.get(opname).unwrap())
-                .set_bits(..=7, displacement.resolve_relative().unwrap_or(0x0).padd()),
+            } => *first_word(opname)
+                .set_bits(
+                    ..=7,
+                    (displacement
+                        .resolve_relative(address, symtable)
+                        .unwrap_or(0x0) as i8)
+                        .padd(),
+                ),
         });
 
         if let Some(value) = match &self {
@@ -173,9 +303,11 @@ impl Instruction {
                 ts,
                 tsd: _,
             } => get_word(ts, symtable),
-            Instruction::Iii { opname:_ , immediate, tsd: _ } => {
-                Some(*immediate)
-            }
+            Instruction::Iii {
+                opname: _,
+                immediate,
+                tsd: _,
+            } => Some(immediate.resolve(symtable).unwrap_or(0)),
             _ => None,
         } {
             result.push(value);
@@ -192,7 +324,11 @@ impl Instruction {
                 shift: _,
                 tsd,
             } => get_word(tsd, symtable),
-            Instruction::Iii { opname: _, immediate: _,tsd } => get_word(tsd, symtable),
+            Instruction::Iii {
+                opname: _,
+                immediate: _,
+                tsd,
+            } => get_word(tsd, symtable),
             Instruction::IV { opname: _, tsd } => get_word(tsd, symtable),
             Instruction::V { opname: _, tsd } => get_word(tsd, symtable),
             _ => None,
@@ -217,3 +353,134 @@ pub fn parse_instruction(input: &str) -> Res<&str, Instruction> {
         )),
     )(input)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::isa::OPCODES;
+    use crate::{disassembler, parser::parse_line};
+
+    const GOLDEN: &str = include_str!("../../res/golden/encoding.txt");
+
+    fn symbols() -> HashMap<String, u16> {
+        HashMap::from([("Table".to_owned(), 0x1022)])
+    }
+
+    /// Golden entries as source line, first word fields and expected words.
+    fn golden() -> Vec<(&'static str, Vec<&'static str>, Vec<u16>)> {
+        GOLDEN
+            .lines()
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .map(|line| {
+                let columns: Vec<&str> = line.split('|').map(str::trim).collect();
+                let fields: Vec<&str> = columns[1].split_whitespace().collect();
+                let mut words = vec![u16::from_str_radix(&fields.concat(), 2).unwrap()];
+                words.extend(
+                    columns[2]
+                        .split_whitespace()
+                        .map(|word| u16::from_str_radix(word, 16).unwrap()),
+                );
+                (columns[0], fields, words)
+            })
+            .collect()
+    }
+
+    fn parse(source: &str) -> Instruction {
+        match parse_line(source) {
+            Ok(("", instruction)) => instruction,
+            other => panic!("cannot parse {:?}: {:?}", source, other),
+        }
+    }
+
+    #[test]
+    fn golden_encoding() {
+        for (source, fields, words) in golden() {
+            let instruction = parse(source);
+            let format = instruction.mnemonic().format;
+            let widths: Vec<usize> = fields.iter().map(|field| field.len()).collect();
+            let layout: Vec<usize> = format.fields().iter().map(|(_, width)| *width).collect();
+            assert_eq!(widths, layout, "{}", source);
+            assert_eq!(instruction.to_binary(0, &symbols()), words, "{}", source);
+        }
+    }
+
+    #[test]
+    fn placed_addresses() {
+        for (source, _, words) in golden() {
+            let instruction = parse(source);
+            for (offset, address) in instruction.placed_addresses() {
+                if let Some(value) = address.resolve(&symbols()) {
+                    assert_eq!(words[usize::from(offset)], value, "{}", source);
+                }
+            }
+        }
+        let instruction = parse("LI 0x1234, @Table");
+        let offsets: Vec<u16> = instruction.placed_addresses().iter().map(|(offset, _)| *offset).collect();
+        assert_eq!(offsets, vec![1, 2]);
+    }
+
+    #[test]
+    fn cycles() {
+        assert_eq!(parse("MOV R1, R2").cycles(), 2);
+        assert_eq!(parse("MOV @Table, *R1+").cycles(), 5);
+        assert_eq!(parse("LI 0x12, 0xA").cycles(), 5);
+        assert_eq!(parse("ROI").cycles(), 4);
+        assert_eq!(parse("JMP -1").cycles(), 2);
+    }
+
+    #[test]
+    fn golden_covers_isa() {
+        let entries = golden();
+        let mut modes = vec![];
+        for (source, _, _) in &entries {
+            match parse(source) {
+                Instruction::I { ts, tsd, .. } => {
+                    modes.push((Format::I, 0, u16::from(&ts)));
+                    modes.push((Format::I, 1, u16::from(&tsd)));
+                }
+                Instruction::II { tsd, .. } => modes.push((Format::II, 1, u16::from(&tsd))),
+                Instruction::Iii { tsd, .. } => modes.push((Format::Iii, 0, u16::from(&tsd))),
+                Instruction::IV { tsd, .. } => modes.push((Format::IV, 0, u16::from(&tsd))),
+                Instruction::V { tsd, .. } => modes.push((Format::V, 0, u16::from(&tsd))),
+                Instruction::VI { .. } => (),
+            }
+        }
+
+        for name in OPCODES.keys() {
+            assert!(
+                entries.iter().any(|(source, _, _)| source.split(' ').next() == Some(name)),
+                "{} is not covered",
+                name
+            );
+        }
+        for mnemonic in OPCODES.values() {
+            for (position, kind) in mnemonic.operands.iter().enumerate() {
+                if matches!(kind, isa::OperandKind::Source | isa::OperandKind::Destination) {
+                    for mode in 0..4 {
+                        let key = (mnemonic.format, position, mode);
+                        assert!(modes.contains(&key), "{:?} is not covered", key);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn unvalidated_encoding() {
+        for source in ["ADD R20, R0", "SLL 20, *R70000", "JNE 300"] {
+            assert_eq!(parse(source).to_binary(0, &symbols()).len(), 1, "{}", source);
+        }
+    }
+
+    #[test]
+    fn decode_round_trip() {
+        for (source, _, words) in golden() {
+            let (instruction, length) = disassembler::decode(&words).unwrap();
+            assert_eq!(length, words.len(), "{}", source);
+            assert_eq!(instruction.to_binary(0, &HashMap::new()), words, "{}", source);
+
+            let text = instruction.to_string();
+            assert_eq!(parse(&text).to_binary(0, &HashMap::new()), words, "{}", text);
+        }
+    }
+}
@@ -1,8 +1,7 @@
-use super::{Instruction, Res};
+use super::{Format, Instruction, Res};
 use crate::parser::operand::parse_operand;
 
 use nom::{
-    branch::alt,
     bytes::complete::tag,
     character::complete::{space0, space1},
     error::context,
@@ -10,20 +9,7 @@ use nom::{
 };
 
 fn opname(input: &str) -> Res<&str, &str> {
-    context(
-        "i opcode name",
-        alt((
-            tag("OR"),
-            tag("AND"),
-            tag("XOR"),
-            tag("CMP"),
-            tag("ADD"),
-            tag("STR"),
-            tag("MUL"),
-            tag("MOV"),
-        )),
-    )(input)
-    .map(|(next_input, res)| (next_input, res))
+    context("i opcode name", super::opname(Format::I, 2))(input)
 }
 
 pub fn parse(input: &str) -> Res<&str, Instruction> {
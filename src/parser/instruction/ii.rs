@@ -1,20 +1,15 @@
-use super::{Instruction, Res};
-use crate::parser::operand::parse_operand;
+use super::{Format, Instruction, Res};
+use crate::parser::{number::parse_unsigned, operand::parse_operand};
 
 use nom::{
-    branch::alt,
     bytes::complete::tag,
-    character::complete::{digit1, space0, space1},
+    character::complete::{space0, space1},
     error::context,
     sequence::tuple,
 };
 
 fn opname(input: &str) -> Res<&str, &str> {
-    context(
-        "ii opcode name",
-        alt((tag("SLL"), tag("SRL"), tag("SLA"), tag("SRA"), tag("ROT"))),
-    )(input)
-    .map(|(next_input, res)| (next_input, res))
+    context("ii opcode name", super::opname(Format::II, 2))(input)
 }
 
 pub fn parse(input: &str) -> Res<&str, Instruction> {
@@ -23,7 +18,7 @@ pub fn parse(input: &str) -> Res<&str, Instruction> {
         tuple((
             opname,
             space1,
-            digit1,
+            parse_unsigned,
             space0,
             tag(","),
             space0,
@@ -35,7 +30,7 @@ pub fn parse(input: &str) -> Res<&str, Instruction> {
             next_input,
             Instruction::II {
                 opname: opname.to_owned(),
-                shift: shift.parse::<u8>().expect("cannot parse to u8"),
+                shift,
                 tsd: operand,
             },
         )
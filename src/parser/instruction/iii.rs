@@ -1,40 +1,21 @@
-use super::{Instruction, Res};
+use super::{Format, Instruction, Res};
 use crate::parser::{
+    immediate::{parse_immediate, Immediate},
     operand::parse_operand,
-    hexadecimal,
 };
 
 use nom::{
-    branch::alt,
-    bytes::complete::tag,
-    character::complete::{space0, space1, char},
+    character::complete::{char, space0, space1},
     error::context,
     sequence::tuple,
 };
 
-fn immediate_value(input: &str) -> Res<&str, u16> {
-    context("immediate raw value", 
-        hexadecimal::i16
-    )(input)
-    .map(|(next_input, address)| (next_input, unsafe { std::mem::transmute(address)}))
+fn immediate_value(input: &str) -> Res<&str, Immediate> {
+    context("immediate raw value", parse_immediate)(input)
 }
 
 fn opname(input: &str) -> Res<&str, &str> {
-    context(
-        "iii opcode name",
-        alt((
-            tag("ORI"),
-            tag("ANDI"),
-            tag("XORI"),
-            tag("CI"),
-            tag("ADDI"),
-            tag("STRI"),
-            tag("MULI"),
-            tag("LI"),
-            tag("LIMI"),
-        )),
-    )(input)
-    .map(|(next_input, res)| (next_input, res))
+    context("iii opcode name", super::opname(Format::Iii, 2))(input)
 }
 
 pub fn parse(input: &str) -> Res<&str, Instruction> {
@@ -50,14 +31,16 @@ pub fn parse(input: &str) -> Res<&str, Instruction> {
             immediate_value,
         )),
     )(input)
-    .map(|(next_input, (opname, _, operand, _, _comma, _, immediate))| {
-        (
-            next_input,
-            Instruction::Iii {
-                opname: opname.to_owned(),
-                immediate,
-                tsd: operand,
-            },
-        )
-    })
+    .map(
+        |(next_input, (opname, _, operand, _, _comma, _, immediate))| {
+            (
+                next_input,
+                Instruction::Iii {
+                    opname: opname.to_owned(),
+                    immediate,
+                    tsd: operand,
+                },
+            )
+        },
+    )
 }
@@ -1,28 +1,13 @@
-use super::{Instruction, Res};
+use super::{Format, Instruction, Res};
 use crate::parser::{
     operand::{parse_operand, Operand},
     register::Register,
 };
 
-use nom::{
-    branch::alt, bytes::complete::tag, character::complete::space1, error::context, sequence::tuple,
-};
+use nom::{branch::alt, character::complete::space1, error::context, sequence::tuple};
 
 fn opname_generic(input: &str) -> Res<&str, &str> {
-    context(
-        "iv opcode name",
-        alt((
-            tag("NOT"),
-            tag("INC"),
-            tag("DEC"),
-            tag("CLR"),
-            tag("PUSH"),
-            tag("PULL"),
-            tag("TST"),
-            tag("SET"),
-        )),
-    )(input)
-    .map(|(next_input, res)| (next_input, res))
+    context("iv opcode name", super::opname(Format::IV, 1))(input)
 }
 
 fn parse_generic(input: &str) -> Res<&str, Instruction> {
@@ -40,7 +25,7 @@ fn parse_generic(input: &str) -> Res<&str, Instruction> {
 }
 
 fn opname_roi(input: &str) -> Res<&str, &str> {
-    context("iv roi opcode name", tag("ROI"))(input).map(|(next_input, res)| (next_input, res))
+    context("iv roi opcode name", super::opname(Format::IV, 0))(input)
 }
 
 fn parse_roi(input: &str) -> Res<&str, Instruction> {
@@ -57,5 +42,4 @@ fn parse_roi(input: &str) -> Res<&str, Instruction> {
 
 pub fn parse(input: &str) -> Res<&str, Instruction> {
     context("iv", alt((parse_generic, parse_roi)))(input)
-        .map(|(next_input, instruction)| (next_input, instruction))
 }
@@ -1,25 +1,10 @@
-use super::{Instruction, Res};
+use super::{Format, Instruction, Res};
 use crate::parser::operand::parse_operand;
 
-use nom::{
-    branch::alt, bytes::complete::tag, character::complete::space1, error::context, sequence::tuple,
-};
+use nom::{character::complete::space1, error::context, sequence::tuple};
 
 fn opname(input: &str) -> Res<&str, &str> {
-    context(
-        "v opcode name",
-        alt((
-            tag("B"),
-            tag("BEQ"),
-            tag("BNE"),
-            tag("BC"),
-            tag("BNC"),
-            tag("BGT"),
-            tag("BLT"),
-            tag("BN"),
-        )),
-    )(input)
-    .map(|(next_input, res)| (next_input, res))
+    context("v opcode name", super::opname(Format::V, 1))(input)
 }
 
 pub fn parse(input: &str) -> Res<&str, Instruction> {
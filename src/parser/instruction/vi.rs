@@ -1,25 +1,10 @@
-use super::{Instruction, Res};
+use super::{Format, Instruction, Res};
 use crate::parser::address::parse_relative;
 
-use nom::{
-    branch::alt, bytes::complete::tag, character::complete::space1, error::context, sequence::tuple,
-};
+use nom::{character::complete::space1, error::context, sequence::tuple};
 
 fn opname(input: &str) -> Res<&str, &str> {
-    context(
-        "vi opcode name",
-        alt((
-            tag("JMP"),
-            tag("JEQ"),
-            tag("JNE"),
-            tag("JC"),
-            tag("JNC"),
-            tag("JGT"),
-            tag("JLT"),
-            tag("JN"),
-        )),
-    )(input)
-    .map(|(next_input, res)| (next_input, res))
+    context("vi opcode name", super::opname(Format::VI, 1))(input)
 }
 
 pub fn parse(input: &str) -> Res<&str, Instruction> {
@@ -1,37 +1,129 @@
 use super::address::parse_raw_value;
+use super::identifier::{parse_identifier, parse_local_identifier};
 use super::Res;
 use nom::{
-    bytes::complete::take_while,
-    character::complete::{char, space0},
+    branch::alt,
+    character::complete::{char, digit1, space0},
     error::context,
-    sequence::{delimited, tuple},
+    sequence::{delimited, terminated, tuple},
 };
 
+use std::fmt;
+
 #[derive(Debug, PartialEq)]
 pub struct Label {
-    pub address: u16,
+    /// Explicit address, the label marks the current location when absent.
+    pub address: Option<u16>,
     pub name: String,
 }
 
+impl fmt::Display for Label {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.address {
+            Some(address) => write!(f, "(0x{:04X}) {}", address, self.name),
+            None => write!(f, "{}:", self.name),
+        }
+    }
+}
+
 impl Label {
-    pub fn new(address: u16, name: String) -> Self {
+    pub fn new(address: Option<u16>, name: String) -> Self {
         Label { address, name }
     }
+
+    /// Local labels are scoped to the preceding global label.
+    pub fn is_local(name: &str) -> bool {
+        name.starts_with('.')
+    }
+
+    /// Name as written in the source, without the occurrence suffix given to
+    /// anonymous labels.
+    pub fn source_name(name: &str) -> &str {
+        name.split('#').next().unwrap_or(name)
+    }
+
+    /// Labels generated by the assembler contain a `#` and cannot be written
+    /// in the source.
+    pub fn is_internal(name: &str) -> bool {
+        name.contains('#')
+    }
+
+    /// Anonymous labels are numeric and referenced as `1b` or `1f`.
+    pub fn is_anonymous(name: &str) -> bool {
+        !name.is_empty() && name.chars().all(|c| c.is_ascii_digit())
+    }
 }
 
 pub fn parse_label_name(input: &str) -> Res<&str, String> {
-    context("label name", take_while(|e: char| e.is_alphabetic()))(input)
-        .map(|(next_input, label)| (next_input, label.to_owned()))
+    context(
+        "label name",
+        alt((parse_local_identifier, digit1, parse_identifier)),
+    )(input)
+    .map(|(next_input, label)| (next_input, label.to_owned()))
 }
 
-pub fn parse_label(input: &str) -> Res<&str, Label> {
+fn parse_pinned_label(input: &str) -> Res<&str, Label> {
     context(
-        "@Address",
+        "pinned label",
         tuple((
             delimited(char('('), parse_raw_value, char(')')),
             space0,
             parse_label_name,
         )),
     )(input)
-    .map(|(next_input, (address, _, tag))| (next_input, Label::new(address, tag)))
+    .map(|(next_input, (address, _, tag))| (next_input, Label::new(Some(address), tag)))
+}
+
+fn parse_placed_label(input: &str) -> Res<&str, Label> {
+    context("placed label", terminated(parse_label_name, char(':')))(input)
+        .map(|(next_input, tag)| (next_input, Label::new(None, tag)))
+}
+
+pub fn parse_label(input: &str) -> Res<&str, Label> {
+    context("label", alt((parse_pinned_label, parse_placed_label)))(input)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pinned() {
+        assert_eq!(
+            parse_label("(0x10) Fin"),
+            Ok(("", Label::new(Some(0x10), "Fin".to_owned())))
+        )
+    }
+
+    #[test]
+    fn placed() {
+        assert_eq!(
+            parse_label("Loop: DEC R0"),
+            Ok((" DEC R0", Label::new(None, "Loop".to_owned())))
+        )
+    }
+
+    #[test]
+    fn local() {
+        assert_eq!(
+            parse_label(".loop:"),
+            Ok(("", Label::new(None, ".loop".to_owned())))
+        )
+    }
+
+    #[test]
+    fn identifier() {
+        assert_eq!(
+            parse_label("(0x20) print_char2"),
+            Ok(("", Label::new(Some(0x20), "print_char2".to_owned())))
+        )
+    }
+
+    #[test]
+    fn anonymous() {
+        assert_eq!(
+            parse_label("1:"),
+            Ok(("", Label::new(None, "1".to_owned())))
+        )
+    }
 }
@@ -0,0 +1,163 @@
+use super::Res;
+use nom::{
+    branch::alt,
+    bytes::complete::{tag, take_while1, take_while_m_n},
+    character::complete::{char, none_of, one_of},
+    combinator::{map_res, opt, value, verify},
+    error::{context, VerboseError, VerboseErrorKind},
+    sequence::{delimited, pair, preceded},
+};
+
+/// Context of the failure reported for digits overflowing 32 bits.
+pub const TOO_LARGE: &str = "number too large";
+
+/// Digits of the given radix, `_` may separate them. Too many digits are a
+/// failure, so that `0x1FFFFFFFF` is not read again as a decimal `0`.
+fn digits(radix: u32) -> impl FnMut(&str) -> Res<&str, u32> {
+    move |input: &str| {
+        let (next_input, digits) = verify(
+            take_while1(|c: char| c.is_digit(radix) || c == '_'),
+            |digits: &str| !digits.starts_with('_'),
+        )(input)?;
+        match u32::from_str_radix(&digits.replace('_', ""), radix) {
+            Ok(value) => Ok((next_input, value)),
+            Err(_) => Err(nom::Err::Failure(VerboseError {
+                errors: vec![(input, VerboseErrorKind::Context(TOO_LARGE))],
+            })),
+        }
+    }
+}
+
+fn parse_radix(input: &str) -> Res<&str, u32> {
+    context(
+        "integer",
+        alt((
+            preceded(alt((tag("0x"), tag("0X"), tag("$"))), digits(16)),
+            preceded(alt((tag("0b"), tag("0B"))), digits(2)),
+            preceded(alt((tag("0o"), tag("0O"))), digits(8)),
+            digits(10),
+        )),
+    )(input)
+}
+
+fn parse_escape(input: &str) -> Res<&str, char> {
+    context(
+        "escape sequence",
+        preceded(
+            char('\\'),
+            alt((
+                value('\n', char('n')),
+                value('\t', char('t')),
+                value('\r', char('r')),
+                value('\0', char('0')),
+                value('\\', char('\\')),
+                value('\'', char('\'')),
+                map_res(
+                    preceded(char('x'), take_while_m_n(2, 2, |c: char| c.is_ascii_hexdigit())),
+                    |hex: &str| u8::from_str_radix(hex, 16).map(char::from),
+                ),
+            )),
+        ),
+    )(input)
+}
+
+fn parse_character(input: &str) -> Res<&str, u32> {
+    context(
+        "character",
+        delimited(char('\''), alt((parse_escape, none_of("\\'"))), char('\'')),
+    )(input)
+    .map(|(next_input, character)| (next_input, character.into()))
+}
+
+/// Decimal, `0x`, `$`, `0b` or `0o` prefixed integer, or character constant.
+pub fn parse_unsigned(input: &str) -> Res<&str, u32> {
+    context("number", alt((parse_character, parse_radix)))(input)
+}
+
+/// Number with an optional sign.
+pub fn parse_signed(input: &str) -> Res<&str, i32> {
+    context(
+        "signed number",
+        map_res(
+            pair(opt(one_of("+-")), parse_unsigned),
+            |(sign, magnitude)| {
+                i32::try_from(magnitude).map(|magnitude| match sign {
+                    Some('-') => -magnitude,
+                    _ => magnitude,
+                })
+            },
+        ),
+    )(input)
+}
+
+/// Unsigned number fitting in 16 bits.
+pub fn parse_u16(input: &str) -> Res<&str, u16> {
+    context("16 bits number", map_res(parse_unsigned, u16::try_from))(input)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decimal() {
+        assert_eq!(parse_unsigned("42,"), Ok((",", 42)));
+        assert_eq!(parse_unsigned("1_000"), Ok(("", 1000)));
+    }
+
+    #[test]
+    fn hexadecimal() {
+        assert_eq!(parse_unsigned("0xffff"), Ok(("", 65535)));
+        assert_eq!(parse_unsigned("0x1"), Ok(("", 1)));
+        assert_eq!(parse_unsigned("$FF"), Ok(("", 255)));
+        assert_eq!(parse_unsigned("0xDEAD_BEEF"), Ok(("", 0xDEAD_BEEF)));
+    }
+
+    #[test]
+    fn binary() {
+        assert_eq!(parse_unsigned("0b1010_0101"), Ok(("", 0b1010_0101)))
+    }
+
+    #[test]
+    fn octal() {
+        assert_eq!(parse_unsigned("0o17"), Ok(("", 0o17)))
+    }
+
+    #[test]
+    fn character() {
+        assert_eq!(parse_unsigned("'A'"), Ok(("", 65)));
+        assert_eq!(parse_unsigned("'\\n'"), Ok(("", 10)));
+        assert_eq!(parse_unsigned("'\\''"), Ok(("", 39)));
+        assert_eq!(parse_unsigned("'\\x7F'"), Ok(("", 127)));
+    }
+
+    #[test]
+    fn too_large() {
+        for source in ["0x1FFFFFFFF", "99999999999", "0b1_0000_0000_0000_0000_0000_0000_0000_0000"] {
+            assert!(
+                matches!(parse_unsigned(source), Err(nom::Err::Failure(_))),
+                "{}",
+                source
+            );
+        }
+        assert_eq!(parse_unsigned("0xFFFFFFFF"), Ok(("", u32::MAX)));
+    }
+
+    #[test]
+    fn leading_separator() {
+        assert!(parse_unsigned("_1").is_err())
+    }
+
+    #[test]
+    fn signed() {
+        assert_eq!(parse_signed("-0x8"), Ok(("", -8)));
+        assert_eq!(parse_signed("+8"), Ok(("", 8)));
+        assert_eq!(parse_signed("-'A'"), Ok(("", -65)));
+    }
+
+    #[test]
+    fn sized() {
+        assert_eq!(parse_u16("0xFFFF"), Ok(("", 0xFFFF)));
+        assert!(parse_u16("0x10000").is_err());
+    }
+}
@@ -1,3 +1,5 @@
+use std::fmt;
+
 use super::{
     address::{parse_address, Address},
     pointer::{parse_incremented_pointer, parse_pointer},
@@ -6,7 +8,7 @@ use super::{
 };
 use nom::{branch::alt, error::context};
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum Operand {
     Direct(Register),
     NextWord(Address),
@@ -25,13 +27,24 @@ impl From<&Operand> for u16 {
     }
 }
 
+impl fmt::Display for Operand {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Operand::Direct(r) => write!(f, "{}", r),
+            Operand::NextWord(address) => write!(f, "{}", address),
+            Operand::Address(r) => write!(f, "*{}", r),
+            Operand::AddressIncrement(r) => write!(f, "*{}+", r),
+        }
+    }
+}
+
 impl Operand {
-    pub fn get_register_value(&self) -> u16 {
+    pub fn get_register_value(&self) -> u32 {
         match self {
-            Operand::Direct(r) => r.0.into(),
+            Operand::Direct(r) => r.0,
             Operand::NextWord(_) => 0,
-            Operand::Address(r) => r.0.into(),
-            Operand::AddressIncrement(r) => r.0.into(),
+            Operand::Address(r) => r.0,
+            Operand::AddressIncrement(r) => r.0,
         }
     }
 }
@@ -56,5 +69,4 @@ pub fn parse_operand(input: &str) -> Res<&str, Operand> {
             parse_address_operand,
         )),
     )(input)
-    .map(|(next_input, operand)| (next_input, operand))
 }
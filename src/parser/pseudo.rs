@@ -0,0 +1,170 @@
+use nom::{
+    bytes::complete::take_while1,
+    character::complete::{char, space0, space1},
+    combinator::{all_consuming, eof, map_opt, opt, verify},
+    error::context,
+    multi::separated_list1,
+    sequence::{delimited, preceded, tuple},
+};
+
+use std::fmt;
+
+use super::address::parse_symbol;
+use super::identifier::{is_identifier_char, is_reserved};
+use super::operand;
+use super::Res;
+use crate::isa::{self, OperandKind, Pseudo};
+
+/// Pseudo instruction as written in the source, before its expansion.
+#[derive(Debug, PartialEq)]
+pub struct PseudoInstruction {
+    pub pseudo: &'static Pseudo,
+    pub operands: Vec<String>,
+}
+
+impl fmt::Display for PseudoInstruction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.operands.is_empty() {
+            write!(f, "{}", self.pseudo.name)
+        } else {
+            write!(f, "{} {}", self.pseudo.name, self.operands.join(", "))
+        }
+    }
+}
+
+impl PseudoInstruction {
+    /// Source of the real instructions, `next` is the symbol standing for
+    /// the address following the expansion.
+    pub fn expand(&self, next: &str) -> Vec<String> {
+        self.pseudo
+            .expansion
+            .iter()
+            .map(|template| {
+                let line = template.replace("{next}", next);
+                self.operands
+                    .iter()
+                    .enumerate()
+                    .fold(line, |line, (index, operand)| {
+                        line.replace(&format!("{{{}}}", index), operand)
+                    })
+            })
+            .collect()
+    }
+
+    /// Whether the expansion refers to the address following it.
+    pub fn uses_next(&self) -> bool {
+        self.pseudo
+            .expansion
+            .iter()
+            .any(|template| template.contains("{next}"))
+    }
+}
+
+fn parse_mnemonic(input: &str) -> Res<&str, &'static Pseudo> {
+    context(
+        "pseudo mnemonic",
+        map_opt(take_while1(is_identifier_char), isa::lookup_pseudo),
+    )(input)
+}
+
+fn parse_operand(input: &str) -> Res<&str, &str> {
+    take_while1(|chr: char| chr != ',' && !chr.is_whitespace())(input)
+}
+
+/// Whether an operand as written can stand for the given kind, labels
+/// being symbols and the others operands of a real instruction.
+fn is_kind(operand: &str, kind: OperandKind) -> bool {
+    match kind {
+        OperandKind::Label => all_consuming(parse_symbol)(operand).is_ok() && !is_reserved(operand),
+        _ => all_consuming(operand::parse_operand)(operand).is_ok(),
+    }
+}
+
+pub fn parse_pseudo(input: &str) -> Res<&str, PseudoInstruction> {
+    context(
+        "pseudo instruction",
+        verify(
+            delimited(
+                space0,
+                tuple((
+                    parse_mnemonic,
+                    opt(preceded(
+                        space1,
+                        separated_list1(
+                            tuple((space0, char(','), space0)),
+                            parse_operand,
+                        ),
+                    )),
+                )),
+                tuple((space0, eof)),
+            ),
+            |(pseudo, operands): &(&Pseudo, Option<Vec<&str>>)| {
+                let operands = operands.as_deref().unwrap_or_default();
+                operands.len() == pseudo.operands.len()
+                    && operands
+                        .iter()
+                        .zip(pseudo.operands)
+                        .all(|(operand, kind)| is_kind(operand, *kind))
+            },
+        ),
+    )(input)
+    .map(|(next_input, (pseudo, operands))| {
+        (
+            next_input,
+            PseudoInstruction {
+                pseudo,
+                operands: operands
+                    .unwrap_or_default()
+                    .into_iter()
+                    .map(str::to_owned)
+                    .collect(),
+            },
+        )
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn operands() {
+        let (_, pseudo) = parse_pseudo("LDA\tR1,  Table").unwrap();
+        assert_eq!(pseudo.pseudo.name, "LDA");
+        assert_eq!(pseudo.operands, vec!["R1", "Table"]);
+        assert_eq!(pseudo.expand("unused"), vec!["LI R1, @Table"]);
+    }
+
+    #[test]
+    fn next() {
+        let (_, pseudo) = parse_pseudo("CALL print").unwrap();
+        assert!(pseudo.uses_next());
+        assert_eq!(
+            pseudo.expand("after"),
+            vec!["LI R15, @after", "PUSH R15", "B @print"]
+        );
+    }
+
+    #[test]
+    fn arity() {
+        assert!(parse_pseudo("RET").is_ok());
+        assert!(parse_pseudo("RET R1").is_err());
+        assert!(parse_pseudo("CALL").is_err());
+    }
+
+    #[test]
+    fn operand_kinds() {
+        assert!(parse_pseudo("CALL .loop").is_ok());
+        assert!(parse_pseudo("BRA 1f").is_ok());
+        assert!(parse_pseudo("LDA *R1+, Table").is_ok());
+        for source in ["CALL 0x100", "CALL *R1", "CALL R1", "BRA -2", "LDA R1, 0x20", "LDA R1, @Table", "CALL Print now"] {
+            assert!(parse_pseudo(source).is_err(), "{}", source);
+        }
+    }
+
+    #[test]
+    fn whole_word() {
+        assert!(parse_pseudo("NOPE").is_err());
+        assert!(parse_pseudo("NOT R1").is_err());
+    }
+}
@@ -1,16 +1,26 @@
+use std::fmt;
+
 use super::Res;
-use nom::{bytes::complete::tag, character::complete::digit1, error::context, sequence::tuple};
+use nom::{
+    bytes::complete::tag, character::complete::digit1, combinator::map_res, error::context,
+    sequence::preceded,
+};
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Register(pub u32);
 
-#[derive(Debug, Eq, PartialEq)]
-pub struct Register(pub u8);
+impl fmt::Display for Register {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "R{}", self.0)
+    }
+}
 
 pub fn parse_register(input: &str) -> Res<&str, Register> {
-    context("register", tuple((tag("R"), digit1)))(input).map(|(next_input, (_r, digit))| {
-        (
-            next_input,
-            Register(digit.parse::<u8>().expect("Cannot convert to int")),
-        )
-    })
+    context(
+        "register",
+        preceded(tag("R"), map_res(digit1, str::parse::<u32>)),
+    )(input)
+    .map(|(next_input, number)| (next_input, Register(number)))
 }
 
 #[cfg(test)]
@@ -0,0 +1,159 @@
+use std::fmt;
+
+use crate::diagnostic::Severity;
+
+/// Size of the address space in words.
+pub const MEMORY_SIZE: u32 = 0x10000;
+
+/// Words placed by a single source line, from `start` up to `end` excluded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Region {
+    pub line: usize,
+    pub start: u32,
+    pub end: u32,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum PlacementError {
+    Overlap { other: usize, start: u32, end: u32 },
+    PastEnd { end: u32, memory_size: u32 },
+}
+
+impl PlacementError {
+    pub fn severity(&self) -> Severity {
+        match self {
+            PlacementError::Overlap { .. } => Severity::Error,
+            PlacementError::PastEnd { .. } => Severity::Warning,
+        }
+    }
+}
+
+impl fmt::Display for PlacementError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PlacementError::Overlap { other, start, end } if end - start == 1 => write!(
+                f,
+                "word 0x{:04X} is also placed by line {}",
+                start, other
+            ),
+            PlacementError::Overlap { other, start, end } => write!(
+                f,
+                "words 0x{:04X} to 0x{:04X} are also placed by line {}",
+                start,
+                end - 1,
+                other
+            ),
+            PlacementError::PastEnd { end, memory_size } => write!(
+                f,
+                "code runs up to 0x{:04X}, past the end of the 0x{:04X} words memory",
+                end - 1,
+                memory_size
+            ),
+        }
+    }
+}
+
+/// Find regions sharing words and regions running past the end of memory.
+pub fn check(regions: &[Region], memory_size: u32) -> Vec<(usize, PlacementError)> {
+    let mut errors = vec![];
+
+    let mut sorted = regions.to_vec();
+    sorted.sort_by_key(|region| (region.start, region.line));
+    let mut placed: Vec<Region> = vec![];
+    for region in sorted {
+        for other in placed.iter().filter(|other| other.end > region.start) {
+            let (first, second) = if other.line < region.line {
+                (other, &region)
+            } else {
+                (&region, other)
+            };
+            errors.push((
+                second.line,
+                PlacementError::Overlap {
+                    other: first.line,
+                    start: region.start,
+                    end: region.end.min(other.end),
+                },
+            ));
+        }
+        placed.retain(|other| other.end > region.start);
+        placed.push(region);
+    }
+
+    for region in regions {
+        if region.end > memory_size {
+            errors.push((
+                region.line,
+                PlacementError::PastEnd {
+                    end: region.end,
+                    memory_size,
+                },
+            ));
+        }
+    }
+
+    errors.sort_by_key(|(line, _)| *line);
+    errors
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn region(line: usize, start: u32, end: u32) -> Region {
+        Region { line, start, end }
+    }
+
+    #[test]
+    fn disjoint() {
+        let regions = [region(1, 0, 2), region(2, 2, 3), region(3, 0x10, 0x12)];
+        assert_eq!(check(&regions, MEMORY_SIZE), vec![]);
+    }
+
+    #[test]
+    fn overlap() {
+        let regions = [region(1, 0, 3), region(2, 3, 4), region(5, 1, 2)];
+        assert_eq!(
+            check(&regions, MEMORY_SIZE),
+            vec![(
+                5,
+                PlacementError::Overlap {
+                    other: 1,
+                    start: 1,
+                    end: 2
+                }
+            )]
+        );
+    }
+
+    #[test]
+    fn earlier_line_placed_after() {
+        let regions = [region(1, 4, 6), region(2, 3, 5)];
+        assert_eq!(
+            check(&regions, MEMORY_SIZE),
+            vec![(
+                2,
+                PlacementError::Overlap {
+                    other: 1,
+                    start: 4,
+                    end: 5
+                }
+            )]
+        );
+    }
+
+    #[test]
+    fn past_end() {
+        let regions = [region(1, 0xFF, 0x101)];
+        assert_eq!(
+            check(&regions, 0x100),
+            vec![(
+                1,
+                PlacementError::PastEnd {
+                    end: 0x101,
+                    memory_size: 0x100
+                }
+            )]
+        );
+    }
+}
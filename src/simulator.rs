@@ -0,0 +1,473 @@
+use std::fmt;
+
+use crate::disassembler;
+use crate::parser::{address::Address, instruction::Instruction, operand::Operand};
+
+/// Words of the address space.
+const MEMORY_SIZE: usize = 0x10000;
+
+/// Condition flags, set by the instructions listed as such in the isa.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Flags {
+    pub zero: bool,
+    pub carry: bool,
+    pub negative: bool,
+    pub overflow: bool,
+}
+
+impl Flags {
+    /// Flags of a result without carry nor overflow, as left by the logic
+    /// instructions.
+    fn logic(result: u16) -> Flags {
+        Flags {
+            zero: result == 0,
+            negative: result & 0x8000 != 0,
+            ..Default::default()
+        }
+    }
+
+    fn add(a: u16, b: u16) -> (u16, Flags) {
+        let (result, carry) = a.overflowing_add(b);
+        let (_, overflow) = (a as i16).overflowing_add(b as i16);
+        (result, Flags { carry, overflow, ..Flags::logic(result) })
+    }
+
+    /// Difference `a - b`, the carry is set by a borrow.
+    fn subtract(a: u16, b: u16) -> (u16, Flags) {
+        let (result, carry) = a.overflowing_sub(b);
+        let (_, overflow) = (a as i16).overflowing_sub(b as i16);
+        (result, Flags { carry, overflow, ..Flags::logic(result) })
+    }
+
+    /// Whether the condition of a jump or branch opcode holds.
+    fn condition(&self, opcode: u16) -> bool {
+        match opcode {
+            0b000 => true,
+            0b001 => self.zero,
+            0b010 => !self.zero,
+            0b011 => self.carry,
+            0b100 => !self.carry,
+            0b101 => !self.zero && self.negative == self.overflow,
+            0b110 => self.negative != self.overflow,
+            _ => self.negative,
+        }
+    }
+}
+
+/// Word read or written on the memory bus.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Access {
+    pub address: u16,
+    pub data: u16,
+    pub write: bool,
+}
+
+/// Instruction executed by the machine.
+#[derive(Debug, PartialEq)]
+pub struct Step {
+    pub address: u16,
+    /// First word of the instruction.
+    pub word: u16,
+    pub instruction: Instruction,
+    /// Bus accesses in order, the fetch of the instruction words first.
+    pub bus: Vec<Access>,
+    pub cycles: u32,
+}
+
+/// Reason the machine could not go on.
+#[derive(Debug, PartialEq)]
+pub enum Fault {
+    InvalidInstruction { address: u16, word: u16 },
+    /// Still running after the given number of steps.
+    Timeout(usize),
+}
+
+impl fmt::Display for Fault {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Fault::InvalidInstruction { address, word } => {
+                write!(f, "invalid instruction 0x{:04X} at 0x{:04X}", word, address)
+            }
+            Fault::Timeout(steps) => write!(f, "still running after {} steps", steps),
+        }
+    }
+}
+
+/// Register or memory word designated by an operand.
+#[derive(Debug, Clone, Copy)]
+enum Location {
+    Register(usize),
+    Memory(u16),
+}
+
+/// Processor and memory running assembled code. `R1` style operands are
+/// registers, the others designate memory words. The stack pointer is a
+/// separate register, the stack grows down from the top of the memory. The
+/// machine halts on `ROI` and on a jump to itself.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Machine {
+    pub pc: u16,
+    pub registers: [u16; 16],
+    pub sp: u16,
+    pub flags: Flags,
+    /// Interrupt mask loaded by `LIMI`, interrupts are not simulated.
+    pub interrupt_mask: u16,
+    pub memory: Vec<u16>,
+    pub halted: bool,
+    /// Cycles taken since the start.
+    pub cycles: u64,
+}
+
+impl Machine {
+    /// Machine with segments of words loaded, starting at `entry`.
+    pub fn new(segments: &[(u16, Vec<u16>)], entry: u16) -> Machine {
+        let mut memory = vec![0; MEMORY_SIZE];
+        for (base, words) in segments {
+            for (offset, word) in words.iter().enumerate() {
+                memory[(usize::from(*base) + offset) % MEMORY_SIZE] = *word;
+            }
+        }
+        Machine {
+            pc: entry,
+            registers: [0; 16],
+            sp: 0,
+            flags: Flags::default(),
+            interrupt_mask: 0,
+            memory,
+            halted: false,
+            cycles: 0,
+        }
+    }
+
+    fn read(&self, location: Location, bus: &mut Vec<Access>) -> u16 {
+        match location {
+            Location::Register(register) => self.registers[register],
+            Location::Memory(address) => {
+                let data = self.memory[usize::from(address)];
+                bus.push(Access { address, data, write: false });
+                data
+            }
+        }
+    }
+
+    fn write(&mut self, location: Location, data: u16, bus: &mut Vec<Access>) {
+        match location {
+            Location::Register(register) => self.registers[register] = data,
+            Location::Memory(address) => {
+                self.memory[usize::from(address)] = data;
+                bus.push(Access { address, data, write: true });
+            }
+        }
+    }
+
+    /// Apply the addressing mode of an operand, incrementing its register
+    /// when asked to.
+    fn locate(&mut self, operand: &Operand) -> Location {
+        match operand {
+            Operand::Direct(register) => Location::Register(register.0 as usize),
+            Operand::NextWord(Address::Raw(address)) => Location::Memory(*address),
+            Operand::NextWord(_) => unreachable!("Decoded addresses are raw"),
+            Operand::Address(register) => Location::Memory(self.registers[register.0 as usize]),
+            Operand::AddressIncrement(register) => {
+                let value = &mut self.registers[register.0 as usize];
+                let address = *value;
+                *value = value.wrapping_add(1);
+                Location::Memory(address)
+            }
+        }
+    }
+
+    /// Execute the instruction at the program counter.
+    pub fn step(&mut self) -> Result<Step, Fault> {
+        let address = self.pc;
+        let words: Vec<u16> = (0..3)
+            .map(|offset| self.memory[usize::from(address.wrapping_add(offset))])
+            .collect();
+        let (instruction, length) =
+            disassembler::decode(&words).ok_or(Fault::InvalidInstruction { address, word: words[0] })?;
+        let mut bus: Vec<Access> = (0..length)
+            .map(|offset| Access {
+                address: address.wrapping_add(offset as u16),
+                data: words[offset],
+                write: false,
+            })
+            .collect();
+        self.pc = address.wrapping_add(length as u16);
+
+        let mnemonic = instruction.mnemonic();
+        let mut flags = self.flags;
+        match &instruction {
+            Instruction::I { opname, ts, tsd } => {
+                let source = self.locate(ts);
+                let source = self.read(source, &mut bus);
+                let destination = self.locate(tsd);
+                let value = match opname.as_str() {
+                    "MOV" => 0,
+                    _ => self.read(destination, &mut bus),
+                };
+                let result;
+                (result, flags) = alu(opname, value, source);
+                if mnemonic.writes_destination() {
+                    self.write(destination, result, &mut bus);
+                }
+            }
+            Instruction::II { opname, shift, tsd } => {
+                let destination = self.locate(tsd);
+                let value = self.read(destination, &mut bus);
+                let result;
+                (result, flags) = shift_by(opname, value, *shift);
+                self.write(destination, result, &mut bus);
+            }
+            Instruction::Iii { opname, immediate, tsd } => {
+                let immediate = immediate.resolve(&Default::default()).unwrap_or_default();
+                let destination = self.locate(tsd);
+                if opname == "LIMI" {
+                    self.interrupt_mask = immediate;
+                } else {
+                    let value = match opname.as_str() {
+                        "LI" => 0,
+                        _ => self.read(destination, &mut bus),
+                    };
+                    let result;
+                    (result, flags) = alu(opname, value, immediate);
+                    if mnemonic.writes_destination() {
+                        self.write(destination, result, &mut bus);
+                    }
+                }
+            }
+            Instruction::IV { opname, tsd } => match opname.as_str() {
+                "ROI" => self.halted = true,
+                "PUSH" => {
+                    let source = self.locate(tsd);
+                    let value = self.read(source, &mut bus);
+                    self.sp = self.sp.wrapping_sub(1);
+                    self.write(Location::Memory(self.sp), value, &mut bus);
+                }
+                "PULL" => {
+                    let value = self.read(Location::Memory(self.sp), &mut bus);
+                    self.sp = self.sp.wrapping_add(1);
+                    let destination = self.locate(tsd);
+                    self.write(destination, value, &mut bus);
+                }
+                _ => {
+                    let destination = self.locate(tsd);
+                    let value = self.read(destination, &mut bus);
+                    let result;
+                    (result, flags) = match opname.as_str() {
+                        "NOT" => (!value, Flags::logic(!value)),
+                        "INC" => Flags::add(value, 1),
+                        "DEC" => Flags::subtract(value, 1),
+                        "CLR" => (0, Flags::logic(0)),
+                        _ => (value, Flags::logic(value)),
+                    };
+                    if mnemonic.writes_destination() {
+                        self.write(destination, result, &mut bus);
+                    }
+                }
+            },
+            Instruction::V { tsd, .. } => {
+                let target = match self.locate(tsd) {
+                    Location::Register(register) => self.registers[register],
+                    Location::Memory(target) => target,
+                };
+                if self.flags.condition(mnemonic.opcode) {
+                    self.pc = target;
+                }
+            }
+            Instruction::VI { displacement, .. } => {
+                if let Address::Relative(displacement) = displacement {
+                    if self.flags.condition(mnemonic.opcode) {
+                        self.pc = self.pc.wrapping_add(*displacement as u16);
+                    }
+                }
+            }
+        }
+        if mnemonic.sets_flags() {
+            self.flags = flags;
+        }
+        if mnemonic.is_jump() && self.pc == address {
+            self.halted = true;
+        }
+
+        let cycles = instruction.cycles();
+        self.cycles += u64::from(cycles);
+        Ok(Step {
+            address,
+            word: words[0],
+            instruction,
+            bus,
+            cycles,
+        })
+    }
+
+    /// Step until the machine halts, `observe` seeing every step. Returns
+    /// the number of steps taken.
+    pub fn run(&mut self, limit: usize, mut observe: impl FnMut(&Machine, &Step)) -> Result<usize, Fault> {
+        let mut steps = 0;
+        while !self.halted {
+            if steps == limit {
+                return Err(Fault::Timeout(limit));
+            }
+            let step = self.step()?;
+            observe(self, &step);
+            steps += 1;
+        }
+        Ok(steps)
+    }
+}
+
+impl fmt::Display for Machine {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let flag = |set: bool, name: char| if set { name } else { '-' };
+        writeln!(
+            f,
+            "PC  {:04X}  SP  {:04X}  flags {}{}{}{}  cycles {}",
+            self.pc,
+            self.sp,
+            flag(self.flags.zero, 'Z'),
+            flag(self.flags.carry, 'C'),
+            flag(self.flags.negative, 'N'),
+            flag(self.flags.overflow, 'V'),
+            self.cycles
+        )?;
+        for row in 0..4 {
+            let registers: Vec<String> = (row * 4..row * 4 + 4)
+                .map(|register| format!("{:<3} {:04X}", format!("R{}", register), self.registers[register]))
+                .collect();
+            writeln!(f, "{}", registers.join("  "))?;
+        }
+        Ok(())
+    }
+}
+
+/// Result and flags of a type I or III instruction on its destination
+/// `a` and its source or immediate `b`.
+fn alu(opname: &str, a: u16, b: u16) -> (u16, Flags) {
+    match opname {
+        "OR" | "ORI" => (a | b, Flags::logic(a | b)),
+        "AND" | "ANDI" => (a & b, Flags::logic(a & b)),
+        "XOR" | "XORI" => (a ^ b, Flags::logic(a ^ b)),
+        "ADD" | "ADDI" => Flags::add(a, b),
+        "CMP" | "CI" | "STR" | "STRI" => Flags::subtract(a, b),
+        "MUL" | "MULI" => {
+            let product = u32::from(a) * u32::from(b);
+            let overflow = product > 0xFFFF;
+            let result = product as u16;
+            (
+                result,
+                Flags {
+                    carry: overflow,
+                    overflow,
+                    ..Flags::logic(result)
+                },
+            )
+        }
+        _ => (b, Flags::logic(b)),
+    }
+}
+
+/// Result and flags of a type II instruction, the carry holds the last bit
+/// shifted out.
+fn shift_by(opname: &str, value: u16, count: u32) -> (u16, Flags) {
+    // Last bit shifted out, none when shifting by 0.
+    let out = |bit: Option<u32>| bit.is_some_and(|bit| bit < 16 && value & (1 << bit) != 0);
+    let (result, carry) = match opname {
+        "SLL" | "SLA" => (
+            value.checked_shl(count).unwrap_or(0),
+            out(count.checked_sub(1).and_then(|_| 16u32.checked_sub(count))),
+        ),
+        "SRL" => (value >> count, out(count.checked_sub(1))),
+        "SRA" => (((value as i16) >> count) as u16, out(count.checked_sub(1))),
+        _ => (value.rotate_right(count), out(count.checked_sub(1))),
+    };
+    let overflow = opname == "SLA" && (result ^ value) & 0x8000 != 0;
+    (
+        result,
+        Flags {
+            carry,
+            overflow,
+            ..Flags::logic(result)
+        },
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::assembler::Program;
+    use crate::output;
+
+    fn run(source: &str) -> Machine {
+        let program = Program::parse(source);
+        assert_eq!(program.diagnostics(), vec![]);
+        let mut machine = Machine::new(&output::segments(&program), 0);
+        machine.run(1000, |_, _| ()).unwrap();
+        machine
+    }
+
+    #[test]
+    fn arithmetic() {
+        let machine = run("LI R0, 7\nLI R1, 3\nSTR R1, R0\nMUL R0, R1\nSLL 2, R0\nADDI R1, -12\nROI\n");
+        assert_eq!(machine.registers[..2], [16, 0]);
+        assert!(machine.flags.zero && machine.flags.carry);
+        assert_eq!(machine.cycles, 3 + 3 + 2 + 4 + 2 + 3 + 4);
+    }
+
+    #[test]
+    fn memory_and_loops() {
+        let machine = run(concat!(
+            "LI R0, 3\nLDA R1, Table\nLDA R2, Copy\n",
+            "Loop: MOV *R1+, *R2+\nDEC R0\nJNE Loop\n",
+            "JMP -1\n(0x40) Table\nROI\n(0x50) Copy\n"
+        ));
+        assert!(machine.halted);
+        assert_eq!(machine.pc, 0x09);
+        assert_eq!(machine.memory[0x50..0x53], machine.memory[0x40..0x43]);
+        assert_eq!(machine.registers[..3], [0, 0x43, 0x53]);
+    }
+
+    #[test]
+    fn subroutines() {
+        let machine = run("LI R0, 1\nCALL Double\nCALL Double\nROI\nDouble: ADD R0, R0\nRET\n");
+        assert_eq!(machine.registers[0], 4);
+        assert_eq!(machine.sp, 0);
+    }
+
+    #[test]
+    fn zero_shift() {
+        for opname in ["SLL", "SRL", "SLA", "SRA", "ROT"] {
+            let machine = run(&format!("LI R1, 0x8001\n{} 0, R1\nROI\n", opname));
+            assert_eq!(machine.registers[1], 0x8001, "{}", opname);
+            assert!(!machine.flags.carry && !machine.flags.overflow, "{}", opname);
+        }
+    }
+
+    #[test]
+    fn bus() {
+        let program = Program::parse("(0x10) Start\nMOV @Value, *R1\n(0x20) Value\n");
+        let mut machine = Machine::new(&output::segments(&program), 0x10);
+        machine.memory[0x20] = 0x1234;
+        let step = machine.step().unwrap();
+        assert_eq!(
+            step.bus,
+            vec![
+                Access { address: 0x10, data: 0x7421, write: false },
+                Access { address: 0x11, data: 0x0020, write: false },
+                Access { address: 0x20, data: 0x1234, write: false },
+                Access { address: 0x00, data: 0x1234, write: true },
+            ]
+        );
+    }
+
+    #[test]
+    fn faults() {
+        let mut machine = Machine::new(&[(0, vec![0xFFFF])], 0);
+        assert_eq!(
+            machine.step(),
+            Err(Fault::InvalidInstruction { address: 0, word: 0xFFFF })
+        );
+        let mut machine = Machine::new(&[(0, vec![0xE200, 0xF2FE])], 0);
+        machine.registers[0] = 100;
+        assert_eq!(machine.run(10, |_, _| ()), Err(Fault::Timeout(10)));
+    }
+}
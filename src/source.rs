@@ -0,0 +1,159 @@
+use std::path::{Path, PathBuf};
+
+use crate::diagnostic::Diagnostic;
+use crate::parser::{self, directive::Directive};
+
+/// Source with its `.include` directives replaced by the included files.
+#[derive(Debug, Default, PartialEq)]
+pub struct Source {
+    pub text: String,
+    /// Every file read, the main one first.
+    pub files: Vec<String>,
+    /// File index and line number of each line of `text`.
+    origins: Vec<(usize, usize)>,
+}
+
+/// Error found while reading a file, attached to the line including it.
+#[derive(Debug, PartialEq)]
+pub struct IncludeError {
+    pub file: String,
+    pub diagnostic: Diagnostic,
+}
+
+impl Source {
+    /// Expand the includes of `text`, read from `path`. Included files are
+    /// looked up next to the including file, then in each of `directories`.
+    pub fn load(
+        path: &str,
+        text: &str,
+        directories: &[PathBuf],
+        read: &dyn Fn(&Path) -> Option<String>,
+    ) -> Result<Source, IncludeError> {
+        let mut source = Source::default();
+        source.include(path, text, directories, read, &mut vec![])?;
+        Ok(source)
+    }
+
+    /// Source made of a single file, its includes left unexpanded.
+    pub fn single(path: &str, text: &str) -> Source {
+        Source {
+            text: text.lines().flat_map(|line| [line, "\n"]).collect(),
+            files: vec![path.to_owned()],
+            origins: (1..=text.lines().count()).map(|line| (0, line)).collect(),
+        }
+    }
+
+    fn include(
+        &mut self,
+        path: &str,
+        text: &str,
+        directories: &[PathBuf],
+        read: &dyn Fn(&Path) -> Option<String>,
+        stack: &mut Vec<String>,
+    ) -> Result<(), IncludeError> {
+        let file = self.files.len();
+        self.files.push(path.to_owned());
+        stack.push(path.to_owned());
+
+        for (number, line) in text.lines().enumerate() {
+            let error = |message: String| IncludeError {
+                file: path.to_owned(),
+                diagnostic: Diagnostic::error(number + 1, message),
+            };
+            let (code, _) = parser::split_comment(line);
+            let Ok((_, Directive::Include(name))) = parser::parse_directive(code) else {
+                self.text.push_str(line);
+                self.text.push('\n');
+                self.origins.push((file, number + 1));
+                continue;
+            };
+
+            let parent = Path::new(path).parent().unwrap_or(Path::new(""));
+            let (included, content) = std::iter::once(parent)
+                .chain(directories.iter().map(PathBuf::as_path))
+                .map(|directory| directory.join(&name))
+                .find_map(|candidate| read(&candidate).map(|content| (candidate, content)))
+                .ok_or_else(|| error(format!("cannot find included file {}", name)))?;
+            let included = included.display().to_string();
+            if stack.contains(&included) {
+                return Err(error(format!("{} includes itself", included)));
+            }
+            self.include(&included, &content, directories, read, stack)?;
+        }
+
+        stack.pop();
+        Ok(())
+    }
+
+    /// File and line number a line of the expanded text comes from.
+    pub fn origin(&self, line: usize) -> (&str, usize) {
+        match self.origins.get(line.wrapping_sub(1)) {
+            Some((file, line)) => (&self.files[*file], *line),
+            None => (&self.files[0], line),
+        }
+    }
+
+    /// Line of the expanded text coming from a line of the main file.
+    pub fn expanded(&self, line: usize) -> Option<usize> {
+        self.origins
+            .iter()
+            .position(|origin| *origin == (0, line))
+            .map(|index| index + 1)
+    }
+
+    /// Text of a line of the expanded source, counted from 1.
+    pub fn line(&self, line: usize) -> &str {
+        self.text.lines().nth(line.wrapping_sub(1)).unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn files(path: &Path) -> Option<String> {
+        match path.to_str()? {
+            "src/uart.asm" => Some(".include \"regs.asm\"\nuart: ROI\n".to_owned()),
+            "lib/regs.asm" => Some("regs: ROI\n".to_owned()),
+            "src/loop.asm" => Some(".include \"loop.asm\"\n".to_owned()),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn nested() {
+        let directories = [PathBuf::from("lib")];
+        let source = Source::load(
+            "src/main.asm",
+            "Start: ROI\n.include \"uart.asm\"\nEnd: ROI\n",
+            &directories,
+            &files,
+        )
+        .unwrap();
+        assert_eq!(source.text, "Start: ROI\nregs: ROI\nuart: ROI\nEnd: ROI\n");
+        assert_eq!(source.files, vec!["src/main.asm", "src/uart.asm", "lib/regs.asm"]);
+        assert_eq!(source.origin(2), ("lib/regs.asm", 1));
+        assert_eq!(source.origin(3), ("src/uart.asm", 2));
+        assert_eq!(source.origin(4), ("src/main.asm", 3));
+        assert_eq!(source.expanded(3), Some(4));
+        assert_eq!(source.line(3), "uart: ROI");
+    }
+
+    #[test]
+    fn errors() {
+        assert_eq!(
+            Source::load("src/main.asm", "ROI\n.include \"missing.asm\"\n", &[], &files),
+            Err(IncludeError {
+                file: "src/main.asm".to_owned(),
+                diagnostic: Diagnostic::error(2, "cannot find included file missing.asm")
+            })
+        );
+        assert_eq!(
+            Source::load("src/loop.asm", ".include \"loop.asm\"\n", &[], &files),
+            Err(IncludeError {
+                file: "src/loop.asm".to_owned(),
+                diagnostic: Diagnostic::error(1, "src/loop.asm includes itself")
+            })
+        );
+    }
+}
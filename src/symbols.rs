@@ -0,0 +1,125 @@
+use std::fmt;
+
+use crate::diagnostic::Severity;
+use crate::parser::label::Label;
+
+#[derive(Debug, PartialEq)]
+pub enum SymbolError {
+    Undefined {
+        name: String,
+        suggestion: Option<String>,
+    },
+    Duplicate {
+        name: String,
+        first: usize,
+    },
+    /// Label named as a symbol of the memory layout.
+    Predefined(String),
+    /// Label named as a constant defined on the command line.
+    Defined(String),
+    Unused(String),
+}
+
+impl SymbolError {
+    pub fn severity(&self) -> Severity {
+        match self {
+            SymbolError::Unused(_) => Severity::Warning,
+            _ => Severity::Error,
+        }
+    }
+}
+
+impl fmt::Display for SymbolError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SymbolError::Undefined { name, suggestion } => {
+                write!(f, "undefined symbol {}", Label::source_name(name))?;
+                if let Some(suggestion) = suggestion {
+                    write!(f, ", did you mean {}?", suggestion)?;
+                }
+                Ok(())
+            }
+            SymbolError::Duplicate { name, first } => write!(
+                f,
+                "label {} is already defined on line {}",
+                Label::source_name(name),
+                first
+            ),
+            SymbolError::Predefined(name) => write!(
+                f,
+                "label {} is already defined by the memory layout",
+                Label::source_name(name)
+            ),
+            SymbolError::Defined(name) => write!(
+                f,
+                "label {} is already defined on the command line",
+                Label::source_name(name)
+            ),
+            SymbolError::Unused(name) => {
+                write!(f, "label {} is never used", Label::source_name(name))
+            }
+        }
+    }
+}
+
+/// Edit distance between two names, counting a transposition of adjacent
+/// characters as a single edit.
+fn distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    let mut table = vec![vec![0; b.len() + 1]; a.len() + 1];
+
+    for (i, row) in table.iter_mut().enumerate() {
+        row[0] = i;
+    }
+    for (j, cell) in table[0].iter_mut().enumerate() {
+        *cell = j;
+    }
+    for i in 1..=a.len() {
+        for j in 1..=b.len() {
+            let cost = usize::from(a[i - 1] != b[j - 1]);
+            let mut best = (table[i - 1][j] + 1)
+                .min(table[i][j - 1] + 1)
+                .min(table[i - 1][j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                best = best.min(table[i - 2][j - 2] + 1);
+            }
+            table[i][j] = best;
+        }
+    }
+
+    table[a.len()][b.len()]
+}
+
+/// Closest known symbol to a misspelled one.
+pub fn suggest<'a>(name: &str, symbols: impl Iterator<Item = &'a String>) -> Option<String> {
+    let threshold = (name.chars().count() / 3).max(1);
+    symbols
+        .filter(|symbol| !Label::is_anonymous(Label::source_name(symbol)))
+        .map(|symbol| (distance(name, symbol), symbol))
+        .filter(|(distance, _)| *distance <= threshold)
+        .min()
+        .map(|(_, symbol)| symbol.clone())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn edit_distance() {
+        assert_eq!(distance("Boucle", "Boucle"), 0);
+        assert_eq!(distance("Bocle", "Boucle"), 1);
+        assert_eq!(distance("kitten", "sitting"), 3);
+        assert_eq!(distance("Tabel", "Table"), 1);
+    }
+
+    #[test]
+    fn suggestion() {
+        let symbols = ["Table".to_owned(), "Afficheur".to_owned(), "1#0".to_owned()];
+        assert_eq!(suggest("Tabel", symbols.iter()), Some("Table".to_owned()));
+        assert_eq!(suggest("Afichur", symbols.iter()), Some("Afficheur".to_owned()));
+        assert_eq!(suggest("Init", symbols.iter()), None);
+        assert_eq!(suggest("1", symbols.iter()), None);
+    }
+}
@@ -0,0 +1,176 @@
+use std::collections::HashMap;
+
+use crate::assembler::{Program, UnitTest};
+use crate::diagnostic::Diagnostic;
+use crate::parser::{directive::Directive, immediate::Immediate, operand::Operand};
+use crate::simulator::{Fault, Machine};
+
+/// Address the routines called by a test return to, the call ends when the
+/// program counter gets there.
+const RETURN: u16 = 0xFFFF;
+
+fn value(value: &Immediate, symbols: &HashMap<String, u16>) -> Result<u16, String> {
+    value
+        .resolve(symbols)
+        .ok_or_else(|| format!("undefined symbol {}", value))
+}
+
+/// Register or memory word of a `.set` or `.expect`.
+fn location<'a>(
+    machine: &'a mut Machine,
+    operand: &Operand,
+    symbols: &HashMap<String, u16>,
+) -> Result<&'a mut u16, String> {
+    match operand {
+        Operand::Direct(register) => machine
+            .registers
+            .get_mut(usize::try_from(register.0).unwrap_or(usize::MAX))
+            .ok_or_else(|| format!("register {} does not exist", register)),
+        Operand::NextWord(address) => {
+            let address = address
+                .resolve(symbols)
+                .ok_or_else(|| format!("undefined symbol {}", address))?;
+            Ok(&mut machine.memory[usize::from(address)])
+        }
+        _ => Err("tests may only set and expect registers and memory words such as @Label".to_owned()),
+    }
+}
+
+/// Run a test on a fresh machine loaded with `segments`. Each `.call` pushes
+/// a return address then runs the routine until it returns or halts, the
+/// steps of the calls being limited to `limit` in total. Returns the number
+/// of steps taken, or the first failure found.
+pub fn run(
+    program: &Program,
+    segments: &[(u16, Vec<u16>)],
+    test: &UnitTest,
+    limit: usize,
+) -> Result<usize, Diagnostic> {
+    let symbols = &program.symbols;
+    let mut machine = Machine::new(segments, RETURN);
+    let mut steps = 0;
+
+    for (line, directive) in &test.body {
+        let error = |message: String| Diagnostic::error(*line, message);
+        match directive {
+            Directive::Set(operand, assigned) => {
+                let assigned = value(assigned, symbols).map_err(error)?;
+                *location(&mut machine, operand, symbols).map_err(error)? = assigned;
+            }
+            Directive::Expect(operand, expected) => {
+                let expected = value(expected, symbols).map_err(error)?;
+                let found = *location(&mut machine, operand, symbols).map_err(error)?;
+                if found != expected {
+                    return Err(error(format!(
+                        "expected {} to be 0x{:04X}, found 0x{:04X}",
+                        operand, expected, found
+                    )));
+                }
+            }
+            Directive::Call(name) => {
+                let target = *symbols
+                    .get(name)
+                    .ok_or_else(|| error(format!("undefined symbol {}", name)))?;
+                machine.registers[15] = RETURN;
+                machine.sp = machine.sp.wrapping_sub(1);
+                machine.memory[usize::from(machine.sp)] = RETURN;
+                machine.pc = target;
+                machine.halted = false;
+
+                while machine.pc != RETURN && !machine.halted {
+                    if steps == limit {
+                        return Err(error(format!("{} in {}", Fault::Timeout(limit), name)));
+                    }
+                    machine
+                        .step()
+                        .map_err(|fault| error(format!("{} in {}", fault, name)))?;
+                    steps += 1;
+                }
+            }
+            _ => (),
+        }
+    }
+    Ok(steps)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::diagnostic::Severity;
+    use crate::output;
+
+    fn run_all(source: &str) -> Vec<Result<usize, Diagnostic>> {
+        let program = Program::parse(source);
+        assert!(program
+            .diagnostics()
+            .iter()
+            .all(|diagnostic| diagnostic.severity == Severity::Warning));
+        let segments = output::segments(&program);
+        program
+            .tests
+            .iter()
+            .map(|test| run(&program, &segments, test, 100))
+            .collect()
+    }
+
+    const ROUTINES: &str = concat!(
+        "Double: ADD R0, R0\nRET\n",
+        "Sum: CLR R0\n.loop: ADD *R1+, R0\nDEC R2\nJNE .loop\nMOV R0, @Result\nRET\n",
+        "Forever: INC R3\nJMP Forever\n",
+        "(0x40) Table\n(0x50) Result\n",
+    );
+
+    #[test]
+    fn passing() {
+        let source = format!(
+            "{}{}",
+            ROUTINES,
+            concat!(
+                ".test double\n.set R0, 3\n.call Double\n.expect R0, 6\n.call Double\n.expect R0, 12\n.test_end\n",
+                ".test sum\n.set @Table, 2\n.set 0x41, 5\n.set R1, @Table\n.set R2, 2\n.call Sum\n",
+                ".expect @Result, 7\n.expect R1, 0x42\n.test_end\n",
+            )
+        );
+        assert_eq!(run_all(&source), vec![Ok(6), Ok(10)]);
+    }
+
+    #[test]
+    fn failing() {
+        let source = format!(
+            "{}{}",
+            ROUTINES,
+            ".test double\n.set R0, 3\n.call Double\n.expect R0, 7\n.test_end\n.test forever\n.call Forever\n.test_end\n"
+        );
+        assert_eq!(
+            run_all(&source),
+            vec![
+                Err(Diagnostic::error(16, "expected R0 to be 0x0007, found 0x0006")),
+                Err(Diagnostic::error(19, "still running after 100 steps in Forever")),
+            ]
+        );
+    }
+
+    #[test]
+    fn syntax() {
+        let program = Program::parse(
+            "Start: ROI\n.call Start\n.test start\n.expect @Stat, 1\nADD R0, R0\n.test_end\n.test_end\n.test open\n",
+        );
+        let messages: Vec<(usize, String)> = program
+            .diagnostics()
+            .into_iter()
+            .map(|diagnostic| (diagnostic.line, diagnostic.message))
+            .collect();
+        assert_eq!(
+            messages,
+            vec![
+                (1, "label Start is never used".to_owned()),
+                (2, ".call outside of a test".to_owned()),
+                (4, "undefined symbol Stat, did you mean Start?".to_owned()),
+                (5, "only .set, .call and .expect may appear in a test".to_owned()),
+                (7, ".test_end without .test".to_owned()),
+                (8, "test open is never ended".to_owned()),
+            ]
+        );
+        assert_eq!(program.tests.len(), 1);
+    }
+}
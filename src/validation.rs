@@ -0,0 +1,213 @@
+use std::{collections::HashMap, fmt};
+
+use crate::parser::{
+    address::Address, identifier::is_reserved, immediate::Immediate, instruction::Instruction,
+    operand::Operand, register::Register,
+};
+
+pub const REGISTERS: u32 = 16;
+const SHIFT_MAX: u32 = 0b1111;
+const IMMEDIATE: std::ops::RangeInclusive<i32> = i16::MIN as i32..=u16::MAX as i32;
+const DISPLACEMENT: std::ops::RangeInclusive<i16> = i8::MIN as i16..=i8::MAX as i16;
+
+/// Instruction that parsed correctly but cannot be encoded.
+#[derive(Debug, PartialEq)]
+pub enum ValidationError {
+    Register(u32),
+    Shift(u32),
+    Immediate(i32),
+    Displacement(i16),
+    IncrementTwice(u32),
+    /// Register incremented by one operand and read by the other.
+    IncrementUsed(u32),
+    Reserved(String),
+}
+
+impl fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ValidationError::Register(number) => write!(
+                f,
+                "register R{} does not exist, registers range from R0 to R{}",
+                number,
+                REGISTERS - 1
+            ),
+            ValidationError::Shift(count) => write!(
+                f,
+                "shift count {} does not fit in 4 bits, it must range from 0 to {}",
+                count, SHIFT_MAX
+            ),
+            ValidationError::Immediate(value) => write!(
+                f,
+                "immediate value {} does not fit in 16 bits, it must range from {} to {}",
+                value,
+                IMMEDIATE.start(),
+                IMMEDIATE.end()
+            ),
+            ValidationError::Displacement(value) => write!(
+                f,
+                "displacement {} does not fit in 8 bits, it must range from {} to {}",
+                value,
+                DISPLACEMENT.start(),
+                DISPLACEMENT.end()
+            ),
+            ValidationError::IncrementTwice(number) => write!(
+                f,
+                "*R{}+ is both source and destination, the increment order is undefined",
+                number
+            ),
+            ValidationError::IncrementUsed(number) => write!(
+                f,
+                "R{0} is incremented by *R{0}+ and used by the other operand, the order is undefined",
+                number
+            ),
+            ValidationError::Reserved(name) => write!(
+                f,
+                "{} is a reserved word and cannot be used as a symbol",
+                name
+            ),
+        }
+    }
+}
+
+fn register(operand: &Operand) -> Option<&Register> {
+    match operand {
+        Operand::Direct(r) | Operand::Address(r) | Operand::AddressIncrement(r) => Some(r),
+        Operand::NextWord(_) => None,
+    }
+}
+
+/// Check every field of an instruction placed at `address` fits its encoding.
+pub fn validate(
+    instruction: &Instruction,
+    address: u16,
+    symbols: &HashMap<String, u16>,
+) -> Vec<ValidationError> {
+    let mut errors = vec![];
+
+    let operands: Vec<&Operand> = match instruction {
+        Instruction::I { ts, tsd, .. } => vec![ts, tsd],
+        Instruction::II { tsd, .. }
+        | Instruction::Iii { tsd, .. }
+        | Instruction::IV { tsd, .. }
+        | Instruction::V { tsd, .. } => vec![tsd],
+        Instruction::VI { .. } => vec![],
+    };
+    for Register(number) in operands.into_iter().filter_map(register) {
+        if *number >= REGISTERS {
+            errors.push(ValidationError::Register(*number));
+        }
+    }
+
+    for address in instruction.addresses() {
+        if let Address::Symbolic(name) | Address::RelativeSymbolic(name) = address {
+            if is_reserved(name) {
+                errors.push(ValidationError::Reserved(name.clone()));
+            }
+        }
+    }
+
+    if let Instruction::I { ts, tsd, .. } = instruction {
+        match (ts, tsd) {
+            (Operand::AddressIncrement(source), Operand::AddressIncrement(destination))
+                if source == destination =>
+            {
+                errors.push(ValidationError::IncrementTwice(source.0))
+            }
+            (Operand::AddressIncrement(incremented), other) | (other, Operand::AddressIncrement(incremented))
+                if register(other) == Some(incremented) =>
+            {
+                errors.push(ValidationError::IncrementUsed(incremented.0))
+            }
+            _ => (),
+        }
+    }
+
+    match instruction {
+        Instruction::II { shift, .. } if *shift > SHIFT_MAX => {
+            errors.push(ValidationError::Shift(*shift))
+        }
+        Instruction::Iii {
+            immediate: Immediate::Value(value),
+            ..
+        } if !IMMEDIATE.contains(value) => errors.push(ValidationError::Immediate(*value)),
+        Instruction::VI { displacement, .. } => match displacement.resolve_relative(address, symbols) {
+            Some(value) if !DISPLACEMENT.contains(&value) => {
+                errors.push(ValidationError::Displacement(value))
+            }
+            _ => (),
+        },
+        _ => (),
+    }
+
+    errors
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse_line;
+
+    fn check(source: &str) -> Vec<ValidationError> {
+        validate(&parse_line(source).unwrap().1, 0, &HashMap::new())
+    }
+
+    #[test]
+    fn valid() {
+        assert_eq!(check("MOV *R1+, *R15+"), vec![]);
+        assert_eq!(check("SLL 15, R2"), vec![]);
+        assert_eq!(check("LI R0, -0x8000"), vec![]);
+        assert_eq!(check("JNE -128"), vec![]);
+    }
+
+    #[test]
+    fn register() {
+        assert_eq!(
+            check("MOV R16, *R99"),
+            vec![ValidationError::Register(16), ValidationError::Register(99)]
+        )
+    }
+
+    #[test]
+    fn shift() {
+        assert_eq!(check("SLA 16, R1"), vec![ValidationError::Shift(16)])
+    }
+
+    #[test]
+    fn immediate() {
+        assert_eq!(check("LI R0, -0x8001"), vec![ValidationError::Immediate(-0x8001)])
+    }
+
+    #[test]
+    fn displacement() {
+        assert_eq!(check("JNE 128"), vec![ValidationError::Displacement(128)]);
+        assert_eq!(check("JNE -0x81"), vec![ValidationError::Displacement(-0x81)])
+    }
+
+    #[test]
+    fn reserved() {
+        assert_eq!(check("JNE R1"), vec![ValidationError::Reserved("R1".to_owned())]);
+        assert_eq!(check("B @MOV"), vec![ValidationError::Reserved("MOV".to_owned())])
+    }
+
+    #[test]
+    fn parsed_out_of_range() {
+        assert_eq!(check("SLL 70000, R1"), vec![ValidationError::Shift(70000)]);
+        assert_eq!(check("MOV R70000, R1"), vec![ValidationError::Register(70000)])
+    }
+
+    #[test]
+    fn increment_twice() {
+        assert_eq!(
+            check("MOV *R3+, *R3+"),
+            vec![ValidationError::IncrementTwice(3)]
+        )
+    }
+
+    #[test]
+    fn increment_used() {
+        assert_eq!(check("MOV *R1+, R1"), vec![ValidationError::IncrementUsed(1)]);
+        assert_eq!(check("ADD *R2, *R2+"), vec![ValidationError::IncrementUsed(2)]);
+        assert_eq!(check("MOV *R1+, *R2"), vec![])
+    }
+}
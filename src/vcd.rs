@@ -0,0 +1,228 @@
+use crate::simulator::{Access, Machine, Step};
+
+/// Value change dump of named signals, as read by waveform viewers such as
+/// GTKWave. Changes are given in time order, only the last value given for
+/// a signal at a time is written.
+pub struct Vcd {
+    text: String,
+    widths: Vec<usize>,
+    values: Vec<Option<u64>>,
+    /// Changes not yet written and their time.
+    pending: Vec<Option<u64>>,
+    time: u64,
+    /// Time of the last changes written.
+    written: Option<u64>,
+}
+
+/// Short identifier of a signal, made of printable characters.
+fn identifier(index: usize) -> String {
+    const FIRST: u8 = b'!';
+    const COUNT: usize = (b'~' - b'!' + 1) as usize;
+
+    let mut identifier = String::new();
+    let mut index = index;
+    loop {
+        identifier.push((FIRST + (index % COUNT) as u8) as char);
+        index /= COUNT;
+        if index == 0 {
+            return identifier;
+        }
+        index -= 1;
+    }
+}
+
+impl Vcd {
+    /// Dump of the signals named with their width in bits, within `scope`.
+    pub fn new(scope: &str, signals: &[(String, usize)]) -> Vcd {
+        let mut text = String::from("$version apassembler $end\n$timescale 1ns $end\n");
+        text.push_str(&format!("$scope module {} $end\n", scope));
+        for (index, (name, width)) in signals.iter().enumerate() {
+            text.push_str(&format!("$var wire {} {} {} $end\n", width, identifier(index), name));
+        }
+        text.push_str("$upscope $end\n$enddefinitions $end\n");
+        Vcd {
+            text,
+            widths: signals.iter().map(|(_, width)| *width).collect(),
+            values: vec![None; signals.len()],
+            pending: vec![None; signals.len()],
+            time: 0,
+            written: None,
+        }
+    }
+
+    /// Write the changes pending at the current time.
+    fn flush(&mut self) {
+        let mut changes = String::new();
+        for index in 0..self.pending.len() {
+            let Some(value) = self.pending[index].take() else {
+                continue;
+            };
+            if self.values[index] == Some(value) {
+                continue;
+            }
+            self.values[index] = Some(value);
+            match self.widths[index] {
+                1 => changes.push_str(&format!("{}{}\n", value, identifier(index))),
+                width => changes.push_str(&format!("b{:0width$b} {}\n", value, identifier(index), width = width)),
+            }
+        }
+        if !changes.is_empty() {
+            self.text.push_str(&format!("#{}\n{}", self.time, changes));
+            self.written = Some(self.time);
+        }
+    }
+
+    /// Give a signal a value from `time` on.
+    pub fn change(&mut self, time: u64, signal: usize, value: u64) {
+        if time != self.time {
+            self.flush();
+            self.time = time;
+        }
+        self.pending[signal] = Some(value);
+    }
+
+    /// The dump ending at `time`.
+    pub fn finish(mut self, time: u64) -> String {
+        self.flush();
+        // `None` orders before any time written.
+        if self.written < Some(time) {
+            self.text.push_str(&format!("#{}\n", time));
+        }
+        self.text
+    }
+}
+
+/// Indices of the signals traced by `Trace`.
+const PC: usize = 0;
+const INSTRUCTION: usize = 1;
+const REGISTERS: usize = 2;
+const SP: usize = 18;
+const FLAGS: usize = 19;
+const BUS_ADDRESS: usize = 23;
+const BUS_DATA: usize = 24;
+const BUS_READ: usize = 25;
+const BUS_WRITE: usize = 26;
+
+/// Trace of a running machine, one time unit per cycle. Each step shows its
+/// address and first word when it starts, its bus accesses on the following
+/// cycles and the registers and flags it leaves once it ends.
+pub struct Trace {
+    vcd: Vcd,
+    time: u64,
+}
+
+impl Trace {
+    pub fn new(machine: &Machine) -> Trace {
+        let mut signals = vec![("pc".to_owned(), 16), ("instruction".to_owned(), 16)];
+        signals.extend((0..16).map(|register| (format!("r{}", register), 16)));
+        signals.push(("sp".to_owned(), 16));
+        for flag in ["zero", "carry", "negative", "overflow"] {
+            signals.push((flag.to_owned(), 1));
+        }
+        for (name, width) in [("bus_address", 16), ("bus_data", 16), ("bus_read", 1), ("bus_write", 1)] {
+            signals.push((name.to_owned(), width));
+        }
+
+        let mut trace = Trace {
+            vcd: Vcd::new("cpu", &signals),
+            time: 0,
+        };
+        trace.vcd.change(0, PC, machine.pc.into());
+        trace.vcd.change(0, INSTRUCTION, 0);
+        trace.state(machine);
+        trace.bus(None);
+        trace
+    }
+
+    /// Registers, stack pointer and flags at the current time.
+    fn state(&mut self, machine: &Machine) {
+        for (register, value) in machine.registers.iter().enumerate() {
+            self.vcd.change(self.time, REGISTERS + register, (*value).into());
+        }
+        self.vcd.change(self.time, SP, machine.sp.into());
+        let flags = machine.flags;
+        for (index, flag) in [flags.zero, flags.carry, flags.negative, flags.overflow].iter().enumerate() {
+            self.vcd.change(self.time, FLAGS + index, (*flag).into());
+        }
+    }
+
+    /// Access on the bus at the current time, the bus is idle with `None`.
+    fn bus(&mut self, access: Option<&Access>) {
+        if let Some(access) = access {
+            self.vcd.change(self.time, BUS_ADDRESS, access.address.into());
+            self.vcd.change(self.time, BUS_DATA, access.data.into());
+        }
+        let (read, write) = access.map_or((false, false), |access| (!access.write, access.write));
+        self.vcd.change(self.time, BUS_READ, read.into());
+        self.vcd.change(self.time, BUS_WRITE, write.into());
+    }
+
+    /// Record a step taken by the machine, now in the state it left.
+    pub fn record(&mut self, machine: &Machine, step: &Step) {
+        let start = self.time;
+        self.vcd.change(start, PC, step.address.into());
+        self.vcd.change(start, INSTRUCTION, step.word.into());
+        for access in &step.bus {
+            self.bus(Some(access));
+            self.time += 1;
+        }
+        self.bus(None);
+
+        self.time = start + u64::from(step.cycles).max(step.bus.len() as u64);
+        self.state(machine);
+    }
+
+    pub fn finish(self) -> String {
+        let time = self.time;
+        self.vcd.finish(time)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn identifiers() {
+        assert_eq!(identifier(0), "!");
+        assert_eq!(identifier(93), "~");
+        assert_eq!(identifier(94), "!!");
+        assert_eq!(identifier(95), "\"!");
+    }
+
+    #[test]
+    fn changes() {
+        let mut vcd = Vcd::new("top", &[("clk".to_owned(), 1), ("data".to_owned(), 4)]);
+        vcd.change(0, 0, 0);
+        vcd.change(0, 1, 5);
+        vcd.change(2, 0, 1);
+        vcd.change(2, 0, 0);
+        vcd.change(3, 1, 5);
+        vcd.change(4, 1, 6);
+        assert!(vcd.finish(6).ends_with(concat!(
+            "$enddefinitions $end\n",
+            "#0\n0!\nb0101 \"\n",
+            "#4\nb0110 \"\n",
+            "#6\n"
+        )));
+    }
+
+    #[test]
+    fn trace() {
+        // LI R0, 0x0002 then ROI.
+        let mut machine = Machine::new(&[(0, vec![0xD000, 0x0002, 0xE600])], 0);
+        let mut trace = Trace::new(&machine);
+        machine.run(10, |machine, step| trace.record(machine, step)).unwrap();
+        let dump = trace.finish();
+        assert!(dump.contains("$var wire 16 # r0 $end\n"));
+        assert!(dump.contains("$var wire 1 ; bus_write $end\n"));
+        assert!(dump.ends_with(concat!(
+            "#1\nb0000000000000001 8\nb0000000000000010 9\n",
+            "#2\n0:\n",
+            "#3\nb0000000000000010 !\nb1110011000000000 \"\nb0000000000000010 #\n",
+            "b0000000000000010 8\nb1110011000000000 9\n1:\n",
+            "#4\n0:\n",
+            "#7\n"
+        )));
+    }
+}